@@ -1,8 +1,82 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::solana_program::sysvar;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::spl_token::native_mint;
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface, TransferChecked,
+};
+use std::cmp::Reverse;
 
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
+/// Accounts per auction in batch_end_auctions, before its creator payment accounts:
+/// auction, token_mint, auction_nft_account, owner_token_account,
+/// auction_token_account, seller_proceeds_account, seller, metadata,
+/// referrer_token_account, referrer, winner_token_account, winner and seller_index
+pub const BATCH_SETTLEMENT_ACCOUNTS: usize = 13;
+
+/// Metaplex Token Metadata program, owner of every NFT metadata account
+pub const METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// SPL Associated Token Account program, required by programmable NFT transfers
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Remaining accounts of a programmable NFT transfer in create_auction and claim_nft:
+/// metadata, edition, owner_token_record, destination_token_record,
+/// authorization_rules_program, authorization_rules, the instructions sysvar,
+/// system_program, associated_token_program and the token metadata program. The
+/// metadata program id stands in for rule set accounts the NFT does not use.
+pub const PROGRAMMABLE_TRANSFER_ACCOUNTS: usize = 10;
+
+/// Bytes reserved for an auction's display name
+pub const MAX_AUCTION_NAME_LEN: usize = 32;
+
+/// Bytes reserved for an auction's off-chain details URI
+pub const MAX_AUCTION_URI_LEN: usize = 200;
+
+/// Seconds after an auction closes before the house authority can pull a stranded
+/// NFT out of escrow with emergency_withdraw
+pub const EMERGENCY_WITHDRAW_TIMELOCK: i64 = 90 * 24 * 60 * 60;
+
+/// Most NFTs create_bundle_auction escrows alongside token_mint, so a lot holds at
+/// most MAX_BUNDLE_MINTS + 1 NFTs
+pub const MAX_BUNDLE_MINTS: usize = 9;
+
+/// Most recent auctions a SellerIndex lists; older ones drop off as new ones are added
+pub const MAX_SELLER_INDEX_AUCTIONS: usize = 32;
+
+/// Most price tiers an auction's increment table may have
+pub const MAX_INCREMENT_TIERS: usize = 8;
+
+/// Most recent bids an auction keeps in its bid_history ring buffer
+pub const BID_HISTORY_LEN: usize = 10;
+
+/// Layout version of Auction accounts written by create_auction and migrate_auction.
+/// New Auction fields are appended after version and this is bumped, so that
+/// migrate_auction can zero-extend accounts written under an older layout.
+pub const AUCTION_VERSION: u8 = 6;
+
+/// Token standard Metaplex assigns to programmable NFTs
+const TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE: u8 = 4;
+
+/// Instruction index of Transfer in the token metadata program
+const METADATA_TRANSFER_INSTRUCTION: u8 = 49;
+
+/// Leading bytes of the instruction data sent to a house's notify_program when a
+/// bidder is outbid, followed by the auction, the outbid bidder and the new price
+pub const OUTBID_NOTIFICATION_TAG: [u8; 8] = *b"outbid\0\0";
+
 #[program]
 pub mod seismic_auction_house {
     use super::*;
@@ -12,365 +86,11023 @@ pub mod seismic_auction_house {
         bump: u8,
         fee_payer_bump: u8,
         treasury_bump: u8,
-        seller_fee_basis_points: u16,
-        requires_sign_off: bool,
-        can_change_sale_price: bool,
+        args: InitializeAuctionHouseArgs,
     ) -> Result<()> {
+        require!(
+            valid_fee_split(
+                args.seller_fee_basis_points,
+                args.protocol_fee_basis_points
+            ),
+            AuctionHouseError::InvalidFeeBasisPoints
+        );
+        require!(
+            args.referral_fee_basis_points <= args.seller_fee_basis_points,
+            AuctionHouseError::InvalidFeeBasisPoints
+        );
+        require!(
+            valid_duration_bounds(args.min_auction_duration, args.max_auction_duration),
+            AuctionHouseError::InvalidAuctionDuration
+        );
+        require!(
+            args.settlement_delay >= 0,
+            AuctionHouseError::InvalidSettlementDelay
+        );
+        require!(
+            args.creation_cancel_window >= 0,
+            AuctionHouseError::InvalidCancelWindow
+        );
+
         let auction_house = &mut ctx.accounts.auction_house;
         auction_house.authority = ctx.accounts.authority.key();
+        auction_house.creator = ctx.accounts.authority.key();
+        auction_house.pending_authority = None;
         auction_house.treasury_mint = ctx.accounts.treasury_mint.key();
         auction_house.auction_house_fee_account = ctx.accounts.auction_house_fee_account.key();
         auction_house.auction_house_treasury = ctx.accounts.auction_house_treasury.key();
         auction_house.fee_withdrawal_destination = ctx.accounts.fee_withdrawal_destination.key();
         auction_house.fee_payer_bump = fee_payer_bump;
         auction_house.treasury_bump = treasury_bump;
-        auction_house.seller_fee_basis_points = seller_fee_basis_points;
-        auction_house.requires_sign_off = requires_sign_off;
-        auction_house.can_change_sale_price = can_change_sale_price;
+        auction_house.seller_fee_basis_points = args.seller_fee_basis_points;
+        auction_house.requires_sign_off = args.requires_sign_off;
+        auction_house.can_change_sale_price = args.can_change_sale_price;
+        auction_house.allow_self_bid = args.allow_self_bid;
+        auction_house.paused = false;
+        auction_house.min_auction_duration = args.min_auction_duration;
+        auction_house.max_auction_duration = args.max_auction_duration;
+        auction_house.settlement_delay = args.settlement_delay;
+        auction_house.allow_bid_retraction = args.allow_bid_retraction;
+        auction_house.protocol_fee_basis_points = args.protocol_fee_basis_points;
+        auction_house.protocol_fee_destination = args.protocol_fee_destination;
+        auction_house.referral_fee_basis_points = args.referral_fee_basis_points;
+        auction_house.allowed_collection = args.allowed_collection;
+        auction_house.treasury_sweep_threshold = args.treasury_sweep_threshold;
+        auction_house.rounding_policy = args.rounding_policy;
+        auction_house.allow_cancel_after_bid = args.allow_cancel_after_bid;
+        auction_house.max_active_auctions_per_seller = args.max_active_auctions_per_seller;
+        auction_house.creation_cancel_window = args.creation_cancel_window;
+        auction_house.fee_payer_model = args.fee_payer_model;
+        auction_house.absolute_max_bid = args.absolute_max_bid.unwrap_or(u64::MAX);
+        auction_house.notify_program = args.notify_program;
         auction_house.bump = bump;
 
         msg!("Auction house initialized successfully");
         Ok(())
     }
 
-    pub fn create_auction(
-        ctx: Context<CreateAuction>,
+    pub fn create_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateAuction<'info>>,
         auction_bump: u8,
-        token_size: u64,
-        minimum_price: u64,
-        end_time: i64,
+        args: CreateAuctionArgs,
     ) -> Result<()> {
-        let auction = &mut ctx.accounts.auction;
-        auction.authority = ctx.accounts.authority.key();
-        auction.token_mint = ctx.accounts.token_mint.key();
-        auction.token_account = ctx.accounts.token_account.key();
-        auction.treasury_mint = ctx.accounts.treasury_mint.key();
-        auction.token_size = token_size;
-        auction.minimum_price = minimum_price;
-        auction.end_time = end_time;
-        auction.current_price = minimum_price;
-        auction.highest_bidder = None;
-        auction.status = AuctionStatus::Active as u8;
-        auction.bump = auction_bump;
+        let accounts = ListingAccounts {
+            auction: &mut ctx.accounts.auction,
+            auction_house: &ctx.accounts.auction_house,
+            registered_mint: ctx.accounts.registered_mint.as_deref(),
+            token_mint: &ctx.accounts.token_mint,
+            metadata: &ctx.accounts.metadata,
+            token_account: &ctx.accounts.token_account,
+            auction_nft_account: &ctx.accounts.auction_nft_account,
+            seller_index: &mut ctx.accounts.seller_index,
+            treasury_mint: &ctx.accounts.treasury_mint,
+            authority: &ctx.accounts.authority,
+            auction_house_authority: ctx.accounts.auction_house_authority.as_ref(),
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: ctx.remaining_accounts,
+        };
+        list_auction(accounts, auction_bump, args)?;
 
         msg!("Auction created successfully");
         Ok(())
     }
 
+    /// create_auction with the NFT escrowed in the auction PDA's associated token
+    /// account rather than a program-derived one, so wallets and explorers can
+    /// derive the escrow from the auction and mint alone
+    pub fn create_auction_with_ata<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateAuctionWithAta<'info>>,
+        auction_bump: u8,
+        args: CreateAuctionArgs,
+    ) -> Result<()> {
+        let accounts = ListingAccounts {
+            auction: &mut ctx.accounts.auction,
+            auction_house: &ctx.accounts.auction_house,
+            registered_mint: ctx.accounts.registered_mint.as_deref(),
+            token_mint: &ctx.accounts.token_mint,
+            metadata: &ctx.accounts.metadata,
+            token_account: &ctx.accounts.token_account,
+            auction_nft_account: &ctx.accounts.auction_nft_account,
+            seller_index: &mut ctx.accounts.seller_index,
+            treasury_mint: &ctx.accounts.treasury_mint,
+            authority: &ctx.accounts.authority,
+            auction_house_authority: ctx.accounts.auction_house_authority.as_ref(),
+            token_program: &ctx.accounts.token_program,
+            remaining_accounts: ctx.remaining_accounts,
+        };
+        list_auction(accounts, auction_bump, args)?;
+
+        msg!("Auction created successfully");
+        Ok(())
+    }
+
+    /// Escrows only the leading bid. Taking the lead refunds the previous leader in
+    /// the same transaction, so outbid bidders never have funds locked and there is
+    /// nothing for them to withdraw. The tradeoff is history: an outbid Bid record
+    /// keeps its timestamp and nonce but its amount is zeroed, so losing amounts live
+    /// only in BidPlaced and BidRefunded events. Sealed auctions cannot work this way
+    /// and escrow every commitment until settlement.
     pub fn place_bid(
         ctx: Context<PlaceBid>,
         bid_amount: u64,
+        max_bid: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<BidResult> {
+        let accounts = BidAccounts {
+            auction: &mut ctx.accounts.auction,
+            auction_house: &ctx.accounts.auction_house,
+            bid: &mut ctx.accounts.bid,
+            bidder: ctx.accounts.bidder.key(),
+            bidder_wallet: Some(&ctx.accounts.bidder),
+            bidder_token_account: ctx.accounts.bidder_token_account.as_ref(),
+            auction_token_account: ctx.accounts.auction_token_account.as_ref(),
+            previous_bid: ctx.accounts.previous_bid.as_mut(),
+            previous_bidder_token_account: ctx.accounts.previous_bidder_token_account.as_ref(),
+            previous_bidder: ctx
+                .accounts
+                .previous_bidder
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            treasury_mint: &ctx.accounts.treasury_mint,
+            referrer: ctx.accounts.referrer.as_ref().map(|referrer| referrer.key()),
+            loyalty: ctx.accounts.loyalty.as_ref(),
+            bidder_escrow: ctx.accounts.bidder_escrow.as_mut(),
+            bidder_escrow_token_account: ctx.accounts.bidder_escrow_token_account.as_ref(),
+            notify_program: ctx
+                .accounts
+                .notify_program
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            token_program: &ctx.accounts.token_program,
+            system_program: &ctx.accounts.system_program,
+        };
+        let result = apply_bid(accounts, bid_amount, max_bid, proof)?;
+
+        msg!("Bid placed successfully");
+        Ok(result)
+    }
+
+    /// Places a bid the bidder signed off-chain, submitted and paid for by a relayer.
+    /// The instruction just before this one must be an Ed25519 program instruction
+    /// verifying the bidder's signature over bid_signature_message. Relayed bids are
+    /// drawn from the bidder's escrow for exactly `amount` and any buyer premium, with
+    /// no proxy ceiling.
+    pub fn place_bid_with_sig(
+        ctx: Context<PlaceBidWithSig>,
+        amount: u64,
+        nonce: u64,
+        expiry: i64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<BidResult> {
+        require!(
+            Clock::get()?.unix_timestamp <= expiry,
+            AuctionHouseError::BidSignatureExpired
+        );
+
+        // Each signature authorizes one bid: it must carry the escrow's next nonce
+        let bidder_escrow = &mut ctx.accounts.bidder_escrow;
+        require!(
+            nonce == bidder_escrow.bid_signature_nonce,
+            AuctionHouseError::InvalidBidNonce
+        );
+        bidder_escrow.bid_signature_nonce = nonce
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        // The referrer is signed over too, or a relayer could name itself on every bid
+        let referrer = ctx.accounts.referrer.as_ref().map(|referrer| referrer.key());
+        let message =
+            bid_signature_message(&ctx.accounts.auction.key(), amount, nonce, expiry, referrer);
+        verify_ed25519_signature(
+            &ctx.accounts.instructions,
+            &ctx.accounts.bidder.key(),
+            &message,
+        )?;
+
+        let accounts = BidAccounts {
+            auction: &mut ctx.accounts.auction,
+            auction_house: &ctx.accounts.auction_house,
+            bid: &mut ctx.accounts.bid,
+            bidder: ctx.accounts.bidder.key(),
+            bidder_wallet: None,
+            bidder_token_account: None,
+            auction_token_account: ctx.accounts.auction_token_account.as_ref(),
+            previous_bid: ctx.accounts.previous_bid.as_mut(),
+            previous_bidder_token_account: ctx.accounts.previous_bidder_token_account.as_ref(),
+            previous_bidder: ctx
+                .accounts
+                .previous_bidder
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            treasury_mint: &ctx.accounts.treasury_mint,
+            referrer,
+            loyalty: ctx.accounts.loyalty.as_ref(),
+            bidder_escrow: Some(&mut ctx.accounts.bidder_escrow),
+            bidder_escrow_token_account: ctx.accounts.bidder_escrow_token_account.as_ref(),
+            notify_program: ctx
+                .accounts
+                .notify_program
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            token_program: &ctx.accounts.token_program,
+            system_program: &ctx.accounts.system_program,
+        };
+        let result = apply_bid(accounts, amount, 0, proof)?;
+
+        msg!("Relayed bid placed successfully");
+        Ok(result)
+    }
+
+    pub fn end_auction<'info>(ctx: Context<'_, '_, 'info, 'info, EndAuction<'info>>) -> Result<()> {
+        let accounts = SettlementAccounts {
+            auction_house: &ctx.accounts.auction_house,
+            auction_nft_account: &ctx.accounts.auction_nft_account,
+            owner_token_account: &ctx.accounts.owner_token_account,
+            auction_token_account: ctx.accounts.auction_token_account.as_ref(),
+            auction_house_treasury: &ctx.accounts.auction_house_treasury,
+            protocol_fee_account: ctx.accounts.protocol_fee_account.as_ref(),
+            seller_proceeds_account: ctx.accounts.seller_proceeds_account.as_ref(),
+            seller: ctx
+                .accounts
+                .seller
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            referrer_token_account: ctx.accounts.referrer_token_account.as_ref(),
+            referrer: ctx
+                .accounts
+                .referrer
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            winner_token_account: ctx.accounts.winner_token_account.as_ref(),
+            winner: ctx
+                .accounts
+                .winner
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            metadata: &ctx.accounts.metadata,
+            creator_accounts: ctx.remaining_accounts,
+            token_mint: &ctx.accounts.token_mint,
+            treasury_mint: &ctx.accounts.treasury_mint,
+            token_program: &ctx.accounts.token_program,
+            treasury_token_program: &ctx.accounts.treasury_token_program,
+        };
+
+        settle_auction(
+            &mut ctx.accounts.auction,
+            &accounts,
+            ctx.accounts.settler.key(),
+            Clock::get()?.unix_timestamp,
+        )?;
+        release_auction_slot(
+            ctx.accounts.seller_index.as_mut(),
+            &mut ctx.accounts.auction,
+        )?;
+
+        // Forward fees above the house's threshold so the treasury does not build up
+        let auction_house = &ctx.accounts.auction_house;
+        if auction_house.treasury_sweep_threshold > 0 {
+            let treasury = &mut ctx.accounts.auction_house_treasury;
+            treasury.reload()?;
+            if treasury.amount > auction_house.treasury_sweep_threshold {
+                let excess = treasury
+                    .amount
+                    .checked_sub(auction_house.treasury_sweep_threshold)
+                    .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+                let destination = ctx
+                    .accounts
+                    .fee_withdrawal_destination
+                    .as_ref()
+                    .ok_or(AuctionHouseError::MissingPaymentAccount)?;
+                transfer_from_auction_house(
+                    auction_house,
+                    treasury,
+                    destination,
+                    &ctx.accounts.treasury_mint,
+                    &ctx.accounts.treasury_token_program,
+                    excess,
+                )?;
+
+                emit!(TreasuryWithdrawn {
+                    auction_house: auction_house.key(),
+                    treasury: treasury.key(),
+                    destination: destination.key(),
+                    amount: excess,
+                    timestamp: Clock::get()?.unix_timestamp,
+                });
+            }
+        }
+
+        msg!("Auction ended successfully");
+        Ok(())
+    }
+
+    /// Bundle auctions pass a (mint, escrow, owner token account) triple per bundled
+    /// mint as remaining accounts so the whole lot is returned together
+    pub fn cancel_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelAuction<'info>>,
     ) -> Result<()> {
         let auction = &mut ctx.accounts.auction;
-        let bid = &mut ctx.accounts.bid;
 
-        // Check if auction is still active
+        // Only auction authority can cancel
         require!(
-            auction.status == AuctionStatus::Active as u8,
-            AuctionHouseError::AuctionNotActive
+            auction.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
         );
 
-        // Check if auction has ended
+        // Active and not yet started auctions can both be cancelled
         require!(
-            Clock::get()?.unix_timestamp < auction.end_time,
-            AuctionHouseError::AuctionEnded
+            auction.is_open_or_pending(),
+            AuctionHouseError::AuctionNotActive
         );
 
-        // Check if bid is higher than current price
+        // Houses can hold sellers to a sale once bidding starts, except in the grace
+        // period after creation for fixing a mistaken listing; sealed commits count
+        // as bids before any is revealed. A cancelled auction is final, so where
+        // cancelling with bids is allowed every bid withdraws its full escrow.
+        let auction_house = &ctx.accounts.auction_house;
         require!(
-            bid_amount > auction.current_price,
-            AuctionHouseError::BidTooLow
+            auction_house.allow_cancel_after_bid
+                || auction.in_cancel_grace_period(
+                    Clock::get()?.unix_timestamp,
+                    auction_house.creation_cancel_window,
+                )?
+                || (auction.highest_bidder.is_none() && auction.bid_count == 0),
+            AuctionHouseError::CannotCancelWithBids
         );
 
-        // Transfer tokens from bidder to auction
-        let transfer_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.bidder_token_account.to_account_info(),
-                to: ctx.accounts.auction_token_account.to_account_info(),
-                authority: ctx.accounts.bidder.to_account_info(),
-            },
-        );
+        // Update auction status
+        auction.status = AuctionStatus::Cancelled;
 
-        token::transfer(transfer_ctx, bid_amount)?;
+        // Return NFT to original owner; programmable NFTs are reclaimed with claim_nft
+        if !auction.programmable {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.owner_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            transfer_bundle(
+                auction,
+                ctx.remaining_accounts,
+                &auction.authority,
+                &ctx.accounts.token_program,
+            )?;
+        }
 
-        // Update auction state
-        auction.current_price = bid_amount;
-        auction.highest_bidder = Some(ctx.accounts.bidder.key());
+        release_auction_slot(ctx.accounts.seller_index.as_mut(), auction)?;
 
-        // Create bid record
-        bid.auction = auction.key();
-        bid.bidder = ctx.accounts.bidder.key();
-        bid.amount = bid_amount;
-        bid.timestamp = Clock::get()?.unix_timestamp;
+        emit!(AuctionCancelled {
+            auction: auction.key(),
+            seller: auction.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-        msg!("Bid placed successfully");
+        msg!("Auction cancelled successfully");
         Ok(())
     }
 
-    pub fn end_auction(ctx: Context<EndAuction>) -> Result<()> {
+    pub fn withdraw_bid(ctx: Context<WithdrawBid>) -> Result<()> {
         let auction = &mut ctx.accounts.auction;
+        let bid = &ctx.accounts.bid;
 
-        // Check if auction has ended
+        // Bids can only be withdrawn once the auction is over
+        require!(auction.is_final(), AuctionHouseError::AuctionStillActive);
+
+        // Return whatever is still escrowed for this bidder
+        let amount = auction.refundable_amount(bid)?;
+        if amount > 0 {
+            withdraw_from_escrow(
+                auction,
+                ctx.accounts.auction_token_account.as_ref(),
+                ctx.accounts.bidder_token_account.as_ref(),
+                Some(ctx.accounts.bidder.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+                amount,
+            )?;
+
+            emit!(BidRefunded {
+                auction: auction.key(),
+                bidder: bid.bidder,
+                amount,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        // The bid account is closed by Anchor, so a second withdrawal cannot succeed
+        msg!("Bid withdrawn successfully");
+        Ok(())
+    }
+
+    pub fn buy_now<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyNow<'info>>,
+        proof: Vec<[u8; 32]>,
+        max_price: u64,
+    ) -> Result<()> {
         require!(
-            Clock::get()?.unix_timestamp >= auction.end_time,
-            AuctionHouseError::AuctionNotEnded
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        ctx.accounts
+            .auction
+            .admission(&ctx.accounts.buyer.key(), &proof)
+            .require_accepted()?;
+
+        let auction = &mut ctx.accounts.auction;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Scheduled auctions open for bidding at start_time
+        auction.activate_if_started(now);
+        require!(
+            auction.status != AuctionStatus::Pending,
+            AuctionHouseError::AuctionNotStarted
         );
 
         // Check if auction is still active
         require!(
-            auction.status == AuctionStatus::Active as u8,
+            auction.is_active(),
             AuctionHouseError::AuctionNotActive
         );
 
-        // Update auction status
-        auction.status = AuctionStatus::Ended as u8;
+        // Check if auction has ended
+        require!(
+            now < auction.end_time,
+            AuctionHouseError::AuctionEnded
+        );
 
-        // Transfer NFT to highest bidder if there is one
-        if let Some(highest_bidder) = auction.highest_bidder {
-            let transfer_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.auction_token_account.to_account_info(),
-                    to: ctx.accounts.bidder_token_account.to_account_info(),
-                    authority: ctx.accounts.auction_authority.to_account_info(),
-                },
-            );
+        require!(
+            auction.auction_type == AuctionType::EnglishAscending as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // Buy now is only offered while bidding has not reached the buy now price
+        require!(
+            auction.buy_now_price > 0
+                && (auction.highest_bidder.is_none()
+                    || auction.buy_now_price > auction.current_price),
+            AuctionHouseError::BuyNowNotAvailable
+        );
+
+        // Revert rather than pay more than the buyer signed for if the price moved
+        require!(
+            auction.buy_now_price <= max_price,
+            AuctionHouseError::PriceExceededMax
+        );
+
+        // Transfer the buy now price from buyer to auction
+        let total = auction.total_for(auction.buy_now_price)?;
+        let payment = auction.escrow_for(auction.buy_now_price)?;
+        deposit_to_escrow(
+            auction,
+            &ctx.accounts.buyer,
+            ctx.accounts.buyer_token_account.as_ref(),
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            &ctx.accounts.system_program,
+            payment,
+        )?;
+
+        // Refund whoever was winning before the auction was bought out
+        refund_highest_bidder(
+            auction,
+            ctx.accounts.previous_bid.as_mut(),
+            ctx.accounts.previous_bidder_token_account.as_ref(),
+            ctx.accounts
+                .previous_bidder
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+        )?;
 
-            token::transfer(transfer_ctx, auction.token_size)?;
+        // Settle the auction at the buy now price
+        auction.status = AuctionStatus::Ended;
+        auction.settled = true;
+        auction.current_price = auction.buy_now_price;
+        auction.highest_bidder = Some(ctx.accounts.buyer.key());
+        auction.referrer = None;
+        auction.winner_rebate_basis_points = ctx
+            .accounts
+            .loyalty
+            .as_ref()
+            .map_or(0, |loyalty| loyalty.rebate_basis_points);
+
+        // The buyer is present, so the NFT is delivered without a separate claim.
+        // Programmable NFTs and bundles are claimed with the accounts they need.
+        if !auction.held_until_claimed() {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.buyer_nft_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            auction.claimed = true;
         }
 
-        msg!("Auction ended successfully");
+        // Pay the seller in the same instruction that delivers the NFT
+        pay_out_sale(
+            auction,
+            &ctx.accounts.auction_house,
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.auction_house_treasury,
+            ctx.accounts.protocol_fee_account.as_ref(),
+            ctx.accounts.seller_proceeds_account.as_ref(),
+            ctx.accounts
+                .seller
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            None,
+            None,
+            ctx.accounts.buyer_token_account.as_ref(),
+            Some(ctx.accounts.buyer.to_account_info()),
+            &ctx.accounts.metadata,
+            ctx.remaining_accounts,
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            total,
+        )?;
+
+        release_auction_slot(ctx.accounts.seller_index.as_mut(), auction)?;
+
+        emit!(BuyNowExecuted {
+            auction: auction.key(),
+            buyer: ctx.accounts.buyer.key(),
+            price: auction.buy_now_price,
+            timestamp: now,
+        });
+
+        msg!("Auction bought now successfully");
         Ok(())
     }
 
-    pub fn cancel_auction(ctx: Context<CancelAuction>) -> Result<()> {
+    pub fn update_sale_price(ctx: Context<UpdateSalePrice>, new_price: u64) -> Result<()> {
         let auction = &mut ctx.accounts.auction;
 
-        // Only auction authority can cancel
+        // Only auction authority can change the price
         require!(
             auction.authority == ctx.accounts.authority.key(),
             AuctionHouseError::Unauthorized
         );
 
-        // Check if auction is still active
         require!(
-            auction.status == AuctionStatus::Active as u8,
+            ctx.accounts.auction_house.can_change_sale_price,
+            AuctionHouseError::CannotChangeSalePrice
+        );
+
+        // Scheduled auctions can be repriced before they open
+        require!(
+            auction.is_open_or_pending(),
             AuctionHouseError::AuctionNotActive
         );
 
-        // Update auction status
-        auction.status = AuctionStatus::Cancelled as u8;
+        // Repricing after a bid would move the goalposts on active bidders
+        require!(auction.bid_count == 0, AuctionHouseError::AuctionHasBids);
 
-        // Return NFT to original owner
-        let transfer_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.auction_token_account.to_account_info(),
-                to: ctx.accounts.owner_token_account.to_account_info(),
-                authority: ctx.accounts.auction_authority.to_account_info(),
-            },
+        // The same floor create_auction enforces: a zero price only behind a reserve
+        require!(
+            new_price > 0 || auction.reserve_price > 0,
+            AuctionHouseError::InvalidAuctionParameters
         );
 
-        token::transfer(transfer_ctx, auction.token_size)?;
+        auction.minimum_price = new_price;
+        auction.current_price = new_price;
 
-        msg!("Auction cancelled successfully");
+        msg!("Sale price updated successfully");
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(bump: u8, fee_payer_bump: u8, treasury_bump: u8)]
-pub struct InitializeAuctionHouse<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = AuctionHouse::LEN,
-        seeds = [b"auction_house", authority.key().as_ref()],
-        bump
-    )]
-    pub auction_house: Account<'info, AuctionHouse>,
-    pub treasury_mint: Account<'info, token::Mint>,
-    #[account(
-        init,
-        payer = authority,
-        token::mint = treasury_mint,
-        token::authority = auction_house,
-        seeds = [b"auction_house_fee_account", auction_house.key().as_ref()],
-        bump = fee_payer_bump
-    )]
-    pub auction_house_fee_account: Account<'info, TokenAccount>,
-    #[account(
-        init,
-        payer = authority,
-        token::mint = treasury_mint,
-        token::authority = auction_house,
-        seeds = [b"auction_house_treasury", auction_house.key().as_ref()],
-        bump = treasury_bump
-    )]
-    pub auction_house_treasury: Account<'info, TokenAccount>,
-    /// CHECK: This is not dangerous because we don't read or write from this account
-    pub fee_withdrawal_destination: UncheckedAccount<'info>,
-    pub authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    pub fn withdraw_from_treasury(ctx: Context<WithdrawFromTreasury>, amount: u64) -> Result<()> {
+        let auction_house = &ctx.accounts.auction_house;
 
-#[derive(Accounts)]
-#[instruction(auction_bump: u8)]
-pub struct CreateAuction<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = Auction::LEN,
-        seeds = [b"auction", token_mint.key().as_ref(), authority.key().as_ref()],
-        bump = auction_bump
-    )]
-    pub auction: Account<'info, Auction>,
-    pub token_mint: Account<'info, token::Mint>,
-    #[account(
-        constraint = token_account.owner == authority.key(),
-        constraint = token_account.mint == token_mint.key()
-    )]
-    pub token_account: Account<'info, TokenAccount>,
-    pub treasury_mint: Account<'info, token::Mint>,
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        // Only auction house authority can withdraw fees
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
 
-#[derive(Accounts)]
-pub struct PlaceBid<'info> {
-    #[account(mut)]
-    pub auction: Account<'info, Auction>,
-    #[account(
-        init,
-        payer = bidder,
-        space = Bid::LEN,
-        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
-        bump
-    )]
-    pub bid: Account<'info, Bid>,
-    #[account(
-        constraint = bidder_token_account.owner == bidder.key(),
-        constraint = bidder_token_account.mint == auction.treasury_mint
-    )]
-    pub bidder_token_account: Account<'info, TokenAccount>,
-    #[account(
-        constraint = auction_token_account.mint == auction.treasury_mint
-    )]
-    pub auction_token_account: Account<'info, TokenAccount>,
-    pub bidder: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        require!(
+            amount <= ctx.accounts.auction_house_treasury.amount,
+            AuctionHouseError::InsufficientFunds
+        );
 
-#[derive(Accounts)]
-pub struct EndAuction<'info> {
-    #[account(mut)]
-    pub auction: Account<'info, Auction>,
-    #[account(
-        constraint = auction_token_account.key() == auction.token_account
-    )]
-    pub auction_token_account: Account<'info, TokenAccount>,
-    #[account(
-        constraint = bidder_token_account.owner == auction.highest_bidder.unwrap(),
-        constraint = bidder_token_account.mint == auction.token_mint
-    )]
-    pub bidder_token_account: Account<'info, TokenAccount>,
-    /// CHECK: This is the auction authority PDA
-    pub auction_authority: UncheckedAccount<'info>,
-    pub token_program: Program<'info, Token>,
-}
+        // The treasury token account is owned by the auction house PDA, which signs the transfer
+        transfer_from_auction_house(
+            auction_house,
+            &ctx.accounts.auction_house_treasury,
+            &ctx.accounts.fee_withdrawal_destination,
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
 
-#[derive(Accounts)]
-pub struct CancelAuction<'info> {
-    #[account(mut)]
-    pub auction: Account<'info, Auction>,
-    #[account(
-        constraint = auction_token_account.key() == auction.token_account
-    )]
-    pub auction_token_account: Account<'info, TokenAccount>,
-    #[account(
-        constraint = owner_token_account.owner == auction.authority,
-        constraint = owner_token_account.mint == auction.token_mint
-    )]
-    pub owner_token_account: Account<'info, TokenAccount>,
-    /// CHECK: This is the auction authority PDA
-    pub auction_authority: UncheckedAccount<'info>,
-    pub authority: Signer<'info>,
-    pub token_program: Program<'info, Token>,
-}
+        emit!(TreasuryWithdrawn {
+            auction_house: auction_house.key(),
+            treasury: ctx.accounts.auction_house_treasury.key(),
+            destination: ctx.accounts.fee_withdrawal_destination.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
 
-#[account]
-pub struct AuctionHouse {
-    pub authority: Pubkey,
-    pub treasury_mint: Pubkey,
-    pub auction_house_fee_account: Pubkey,
-    pub auction_house_treasury: Pubkey,
-    pub fee_withdrawal_destination: Pubkey,
-    pub fee_payer_bump: u8,
-    pub treasury_bump: u8,
-    pub seller_fee_basis_points: u16,
-    pub requires_sign_off: bool,
-    pub can_change_sale_price: bool,
-    pub bump: u8,
-}
+        msg!("Treasury withdrawal successful");
+        Ok(())
+    }
 
-#[account]
-pub struct Auction {
-    pub authority: Pubkey,
-    pub token_mint: Pubkey,
-    pub token_account: Pubkey,
+    pub fn withdraw_from_fee_account(
+        ctx: Context<WithdrawFromFeeAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        let auction_house = &ctx.accounts.auction_house;
+
+        // Only auction house authority can withdraw from the fee account
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            amount <= ctx.accounts.auction_house_fee_account.amount,
+            AuctionHouseError::InsufficientFunds
+        );
+
+        // Like the treasury, the fee account is owned by the auction house PDA
+        transfer_from_auction_house(
+            auction_house,
+            &ctx.accounts.auction_house_fee_account,
+            &ctx.accounts.fee_withdrawal_destination,
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        emit!(TreasuryWithdrawn {
+            auction_house: auction_house.key(),
+            treasury: ctx.accounts.auction_house_fee_account.key(),
+            destination: ctx.accounts.fee_withdrawal_destination.key(),
+            amount,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Fee account withdrawal successful");
+        Ok(())
+    }
+
+    pub fn close_bid(ctx: Context<WithdrawBid>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let bid = &ctx.accounts.bid;
+        let is_highest_bidder = auction.highest_bidder == Some(bid.bidder);
+
+        if auction.is_active() {
+            // Outbid bidders were refunded when outbid, but the leader cannot retract
+            // and sealed collateral stays locked until settlement
+            require!(
+                auction.auction_type == AuctionType::EnglishAscending as u8
+                    && !is_highest_bidder,
+                AuctionHouseError::AuctionStillActive
+            );
+        }
+
+        // Return whatever is still escrowed for this bidder
+        let amount = auction.refundable_amount(bid)?;
+        if amount > 0 {
+            withdraw_from_escrow(
+                auction,
+                ctx.accounts.auction_token_account.as_ref(),
+                ctx.accounts.bidder_token_account.as_ref(),
+                Some(ctx.accounts.bidder.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+                amount,
+            )?;
+
+            emit!(BidRefunded {
+                auction: auction.key(),
+                bidder: bid.bidder,
+                amount,
+                timestamp: Clock::get()?.unix_timestamp,
+            });
+        }
+
+        // Anchor closes the bid account and returns its rent to the bidder
+        msg!("Bid closed successfully");
+        Ok(())
+    }
+
+    pub fn buy_dutch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BuyNow<'info>>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        ctx.accounts
+            .auction
+            .admission(&ctx.accounts.buyer.key(), &proof)
+            .require_accepted()?;
+
+        let auction = &mut ctx.accounts.auction;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Scheduled auctions open for bidding at start_time
+        auction.activate_if_started(now);
+        require!(
+            auction.status != AuctionStatus::Pending,
+            AuctionHouseError::AuctionNotStarted
+        );
+
+        // Check if auction is still active
+        require!(
+            auction.is_active(),
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Check if auction has ended
+        require!(
+            now < auction.end_time,
+            AuctionHouseError::AuctionEnded
+        );
+
+        require!(
+            auction.auction_type == AuctionType::DutchDescending as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // The first buyer pays the price at the moment their transaction lands
+        let price = auction.dutch_price(now)?;
+        let payment = price
+            .checked_add(auction.buyer_premium(price)?)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        deposit_to_escrow(
+            auction,
+            &ctx.accounts.buyer,
+            ctx.accounts.buyer_token_account.as_ref(),
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            &ctx.accounts.system_program,
+            payment,
+        )?;
+
+        // Settle the auction at the current Dutch price
+        auction.status = AuctionStatus::Ended;
+        auction.settled = true;
+        auction.current_price = price;
+        auction.highest_bidder = Some(ctx.accounts.buyer.key());
+        auction.winner_rebate_basis_points = ctx
+            .accounts
+            .loyalty
+            .as_ref()
+            .map_or(0, |loyalty| loyalty.rebate_basis_points);
+
+        // The buyer is present, so the NFT is delivered without a separate claim.
+        // Programmable NFTs and bundles are claimed with the accounts they need.
+        if !auction.held_until_claimed() {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.buyer_nft_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            auction.claimed = true;
+        }
+
+        // Pay the seller in the same instruction that delivers the NFT
+        pay_out_sale(
+            auction,
+            &ctx.accounts.auction_house,
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.auction_house_treasury,
+            ctx.accounts.protocol_fee_account.as_ref(),
+            ctx.accounts.seller_proceeds_account.as_ref(),
+            ctx.accounts
+                .seller
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            None,
+            None,
+            ctx.accounts.buyer_token_account.as_ref(),
+            Some(ctx.accounts.buyer.to_account_info()),
+            &ctx.accounts.metadata,
+            ctx.remaining_accounts,
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            price,
+        )?;
+        release_auction_slot(ctx.accounts.seller_index.as_mut(), auction)?;
+
+        emit!(AuctionEnded {
+            auction: auction.key(),
+            winner: auction.highest_bidder,
+            settler: ctx.accounts.buyer.key(),
+            price,
+            timestamp: now,
+        });
+
+        msg!("Dutch auction bought successfully");
+        Ok(())
+    }
+
+    pub fn commit_bid(
+        ctx: Context<CommitBid>,
+        commitment: [u8; 32],
+        collateral: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        ctx.accounts
+            .auction
+            .admission(&ctx.accounts.bidder.key(), &proof)
+            .require_accepted()?;
+
+        let auction = &mut ctx.accounts.auction;
+        let bid = &mut ctx.accounts.bid;
+        let bidder_key = ctx.accounts.bidder.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        // Scheduled auctions open for bidding at start_time
+        auction.activate_if_started(now);
+        require!(
+            auction.status != AuctionStatus::Pending,
+            AuctionHouseError::AuctionNotStarted
+        );
+
+        // Check if auction is still active
+        require!(
+            auction.is_active(),
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Commitments close at end_time, when the reveal window opens
+        require!(
+            now < auction.end_time,
+            AuctionHouseError::AuctionEnded
+        );
+
+        require!(
+            auction.auction_type == AuctionType::SealedSecondPrice as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // Sellers may not bid up their own listing unless the house allows it
+        require!(
+            ctx.accounts.auction_house.allow_self_bid || bidder_key != auction.authority,
+            AuctionHouseError::SelfBiddingNotAllowed
+        );
+
+        // A record left by an earlier auction at this address holds nothing here
+        if auction.is_stale_bid(bid) {
+            bid.clear_stale();
+        }
+
+        // Each bidder commits once, so a commitment cannot be swapped after others are seen
+        require!(bid.amount == 0, AuctionHouseError::BidAlreadyCommitted);
+
+        // Collateral caps the amount that can later be revealed
+        require!(
+            collateral > 0 && collateral >= auction.minimum_price,
+            AuctionHouseError::BidTooLow
+        );
+        require!(
+            collateral <= ctx.accounts.auction_house.absolute_max_bid,
+            AuctionHouseError::BidExceedsCeiling
+        );
+
+        deposit_to_escrow(
+            auction,
+            &ctx.accounts.bidder,
+            ctx.accounts.bidder_token_account.as_ref(),
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            collateral,
+        )?;
+
+        auction.bid_count = auction
+            .bid_count
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        auction.last_bid_time = now;
+
+        bid.auction = auction.key();
+        bid.bidder = bidder_key;
+        bid.amount = collateral;
+        bid.timestamp = now;
+        bid.commitment = commitment;
+        bid.revealed = false;
+
+        emit!(BidCommitted {
+            auction: auction.key(),
+            bidder: bidder_key,
+            collateral,
+            timestamp: now,
+        });
+
+        msg!("Bid committed successfully");
+        Ok(())
+    }
+
+    pub fn reveal_bid(ctx: Context<RevealBid>, amount: u64, nonce: [u8; 32]) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let bid = &mut ctx.accounts.bid;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(
+            auction.auction_type == AuctionType::SealedSecondPrice as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // Check if auction is still active
+        require!(
+            auction.is_active(),
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Bids are revealed between end_time and reveal_end_time
+        require!(
+            now >= auction.end_time,
+            AuctionHouseError::AuctionNotEnded
+        );
+        require!(
+            now < auction.reveal_end_time,
+            AuctionHouseError::RevealWindowClosed
+        );
+
+        require!(!bid.revealed, AuctionHouseError::BidAlreadyRevealed);
+
+        // Commitments are sha256(amount.to_le_bytes() || nonce)
+        let hash = hashv(&[&amount.to_le_bytes(), &nonce]);
+        require!(
+            hash.to_bytes() == bid.commitment,
+            AuctionHouseError::CommitmentMismatch
+        );
+
+        // The revealed bid must be backed by the collateral escrowed at commit time
+        require!(amount <= bid.amount, AuctionHouseError::InsufficientFunds);
+        require!(
+            amount >= auction.minimum_price,
+            AuctionHouseError::BidTooLow
+        );
+        require!(
+            amount >= auction.min_bid_raw,
+            AuctionHouseError::BidBelowMinimumUnit
+        );
+        // The ceiling may have been lowered since the collateral was committed
+        require!(
+            amount <= ctx.accounts.auction_house.absolute_max_bid,
+            AuctionHouseError::BidExceedsCeiling
+        );
+
+        bid.revealed = true;
+
+        // Track the top two revealed bids; on a tie the earlier reveal keeps the lead
+        if auction.highest_bidder.is_none() || amount > auction.current_price {
+            if auction.highest_bidder.is_some() {
+                auction.second_highest_bid = auction.current_price;
+            }
+            auction.current_price = amount;
+            auction.highest_bidder = Some(bid.bidder);
+        } else if amount > auction.second_highest_bid {
+            auction.second_highest_bid = amount;
+        }
+
+        emit!(BidRevealed {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount,
+            timestamp: now,
+        });
+
+        msg!("Bid revealed successfully");
+        Ok(())
+    }
+
+    pub fn claim_nft<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimNft<'info>>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let now = Clock::get()?.unix_timestamp;
+
+        if auction.status == AuctionStatus::Ended && auction.highest_bidder.is_some() {
+            // Delivery waits out the house settlement window, even for sales settled
+            // early by buy now
+            require!(
+                now >= auction.settles_at(ctx.accounts.auction_house.settlement_delay)?,
+                AuctionHouseError::SettlementWindowNotElapsed
+            );
+
+            require!(
+                auction.highest_bidder == Some(ctx.accounts.winner.key()),
+                AuctionHouseError::Unauthorized
+            );
+        } else {
+            // Unsold programmable NFTs and bundles stay in escrow until their seller
+            // reclaims them; anything else only has an NFT to claim after a sale
+            require!(
+                auction.held_until_claimed() && auction.is_final(),
+                AuctionHouseError::AuctionNotEnded
+            );
+            require!(
+                auction.authority == ctx.accounts.winner.key(),
+                AuctionHouseError::Unauthorized
+            );
+        }
+
+        require!(!auction.claimed, AuctionHouseError::NftAlreadyClaimed);
+
+        // Delivery is held while the sale is disputed
+        require!(!auction.frozen, AuctionHouseError::AuctionFrozen);
+        auction.claimed = true;
+
+        if auction.programmable {
+            let auction_seeds = &[
+                b"auction".as_ref(),
+                auction.token_mint.as_ref(),
+                auction.original_authority.as_ref(),
+                &[auction.bump],
+            ];
+            transfer_programmable(
+                ctx.remaining_accounts,
+                &ProgrammableTransfer {
+                    source: ctx.accounts.auction_nft_account.to_account_info(),
+                    source_owner: auction.to_account_info(),
+                    destination: ctx.accounts.winner_token_account.to_account_info(),
+                    destination_owner: ctx.accounts.winner.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    authority: auction.to_account_info(),
+                    payer: ctx.accounts.winner.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                auction.token_size,
+                &[&auction_seeds[..]],
+            )?;
+        } else {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.winner_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            transfer_bundle(
+                auction,
+                ctx.remaining_accounts,
+                &ctx.accounts.winner.key(),
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        msg!("NFT claimed successfully");
+        Ok(())
+    }
+
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let auction_house = &mut ctx.accounts.auction_house;
+
+        // Only auction house authority can pause or resume the house
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        auction_house.paused = paused;
+
+        msg!("Auction house paused state updated successfully");
+        Ok(())
+    }
+
+    pub fn transfer_authority(
+        ctx: Context<TransferAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let auction_house = &mut ctx.accounts.auction_house;
+
+        // Only the current authority can propose a successor
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // Nothing changes until the new authority accepts, so a mistyped key can
+        // simply be replaced by proposing again
+        auction_house.pending_authority = Some(new_authority);
+
+        msg!("Authority transfer proposed successfully");
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let auction_house = &mut ctx.accounts.auction_house;
+        let new_authority = ctx.accounts.new_authority.key();
+
+        require!(
+            auction_house.pending_authority == Some(new_authority),
+            AuctionHouseError::Unauthorized
+        );
+
+        let old_authority = auction_house.authority;
+        auction_house.authority = new_authority;
+        auction_house.pending_authority = None;
+
+        emit!(AuthorityTransferred {
+            auction_house: auction_house.key(),
+            old_authority,
+            new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Authority transferred successfully");
+        Ok(())
+    }
+
+    pub fn update_auction_house(
+        ctx: Context<UpdateAuctionHouse>,
+        args: UpdateAuctionHouseArgs,
+    ) -> Result<()> {
+        let auction_house = &mut ctx.accounts.auction_house;
+
+        // Only auction house authority can change house settings
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // New rates apply to every sale settled from now on, including open auctions
+        if let Some(seller_fee_basis_points) = args.seller_fee_basis_points {
+            auction_house.seller_fee_basis_points = seller_fee_basis_points;
+        }
+        if let Some(protocol_fee_basis_points) = args.protocol_fee_basis_points {
+            auction_house.protocol_fee_basis_points = protocol_fee_basis_points;
+        }
+        if let Some(protocol_fee_destination) = args.protocol_fee_destination {
+            auction_house.protocol_fee_destination = protocol_fee_destination;
+        }
+        if let Some(referral_fee_basis_points) = args.referral_fee_basis_points {
+            auction_house.referral_fee_basis_points = referral_fee_basis_points;
+        }
+        if let Some(allowed_collection) = args.allowed_collection {
+            auction_house.allowed_collection = allowed_collection;
+        }
+        require!(
+            valid_fee_split(
+                auction_house.seller_fee_basis_points,
+                auction_house.protocol_fee_basis_points
+            ) && auction_house.referral_fee_basis_points <= auction_house.seller_fee_basis_points,
+            AuctionHouseError::InvalidFeeBasisPoints
+        );
+        if let Some(requires_sign_off) = args.requires_sign_off {
+            auction_house.requires_sign_off = requires_sign_off;
+        }
+        if let Some(can_change_sale_price) = args.can_change_sale_price {
+            auction_house.can_change_sale_price = can_change_sale_price;
+        }
+        if let Some(allow_self_bid) = args.allow_self_bid {
+            auction_house.allow_self_bid = allow_self_bid;
+        }
+        if let Some(fee_withdrawal_destination) = args.fee_withdrawal_destination {
+            auction_house.fee_withdrawal_destination = fee_withdrawal_destination;
+        }
+        if let Some(min_auction_duration) = args.min_auction_duration {
+            auction_house.min_auction_duration = min_auction_duration;
+        }
+        if let Some(max_auction_duration) = args.max_auction_duration {
+            auction_house.max_auction_duration = max_auction_duration;
+        }
+        if let Some(settlement_delay) = args.settlement_delay {
+            require!(
+                settlement_delay >= 0,
+                AuctionHouseError::InvalidSettlementDelay
+            );
+            auction_house.settlement_delay = settlement_delay;
+        }
+        if let Some(allow_bid_retraction) = args.allow_bid_retraction {
+            auction_house.allow_bid_retraction = allow_bid_retraction;
+        }
+        if let Some(treasury_sweep_threshold) = args.treasury_sweep_threshold {
+            auction_house.treasury_sweep_threshold = treasury_sweep_threshold;
+        }
+        if let Some(rounding_policy) = args.rounding_policy {
+            auction_house.rounding_policy = rounding_policy;
+        }
+        if let Some(allow_cancel_after_bid) = args.allow_cancel_after_bid {
+            auction_house.allow_cancel_after_bid = allow_cancel_after_bid;
+        }
+        if let Some(max_active_auctions_per_seller) = args.max_active_auctions_per_seller {
+            auction_house.max_active_auctions_per_seller = max_active_auctions_per_seller;
+        }
+        if let Some(creation_cancel_window) = args.creation_cancel_window {
+            require!(
+                creation_cancel_window >= 0,
+                AuctionHouseError::InvalidCancelWindow
+            );
+            auction_house.creation_cancel_window = creation_cancel_window;
+        }
+        if let Some(fee_payer_model) = args.fee_payer_model {
+            auction_house.fee_payer_model = fee_payer_model;
+        }
+        if let Some(absolute_max_bid) = args.absolute_max_bid {
+            auction_house.absolute_max_bid = absolute_max_bid;
+        }
+        if let Some(notify_program) = args.notify_program {
+            auction_house.notify_program = notify_program;
+        }
+        require!(
+            valid_duration_bounds(
+                auction_house.min_auction_duration,
+                auction_house.max_auction_duration
+            ),
+            AuctionHouseError::InvalidAuctionDuration
+        );
+
+        msg!("Auction house updated successfully");
+        Ok(())
+    }
+
+    pub fn get_auction_info(ctx: Context<GetAuctionInfo>) -> Result<AuctionInfo> {
+        let auction = &ctx.accounts.auction;
+        let now = Clock::get()?.unix_timestamp;
+
+        let seconds_remaining = u64::try_from(auction.end_time.saturating_sub(now).max(0))
+            .map_err(|_| AuctionHouseError::ArithmeticOverflow)?;
+        let current_price = if auction.auction_type == AuctionType::DutchDescending as u8 {
+            auction.dutch_price(now)?
+        } else {
+            auction.current_price
+        };
+
+        Ok(AuctionInfo {
+            status: auction.effective_status(now),
+            seconds_remaining,
+            current_price,
+            min_next_bid: auction.minimum_next_bid()?,
+        })
+    }
+
+    /// Checks whether place_bid would accept a bid right now, without moving funds.
+    /// Covers the auction's and house's rules for the bid; whether the bidder can
+    /// pay for it and passes the right accounts is only known when it is placed.
+    pub fn simulate_bid(
+        ctx: Context<SimulateBid>,
+        bid_amount: u64,
+        max_bid: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<BidCheck> {
+        check_bid(
+            &ctx.accounts.auction_house,
+            &ctx.accounts.auction,
+            &ctx.accounts.bidder.key(),
+            ctx.accounts
+                .bid
+                .as_ref()
+                .map_or(0, |bid| ctx.accounts.auction.standing_bid(bid)),
+            bid_amount,
+            max_bid,
+            &proof,
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    pub fn batch_end_auctions<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchEndAuctions<'info>>,
+        creator_counts: Vec<u8>,
+    ) -> Result<()> {
+        let program_id = ctx.program_id;
+        let auction_house_key = ctx.accounts.auction_house.key();
+        let settler = ctx.accounts.settler.key();
+        let now = Clock::get()?.unix_timestamp;
+
+        // remaining_accounts holds one group per auction: BATCH_SETTLEMENT_ACCOUNTS
+        // accounts followed by creator_counts[i] creator payment accounts, with the
+        // program id standing in for accounts a native SOL auction does not use.
+        // A failed transfer still reverts the whole batch, so auctions that are not
+        // ready to settle are skipped before anything moves.
+        let mut remaining = ctx.remaining_accounts;
+        let mut settled_count: u32 = 0;
+        for creator_count in creator_counts {
+            let group_len = BATCH_SETTLEMENT_ACCOUNTS + creator_count as usize;
+            require!(
+                remaining.len() >= group_len,
+                AuctionHouseError::InvalidBatchAccounts
+            );
+            let (group, rest) = remaining.split_at(group_len);
+            remaining = rest;
+
+            // Account::try_from checks the owner and discriminator; the seeds confirm
+            // this is the canonical auction PDA for its mint and seller
+            let mut auction = Account::<Auction>::try_from(&group[0])?;
+            let expected_auction = Pubkey::create_program_address(
+                &[
+                    b"auction",
+                    auction.token_mint.as_ref(),
+                    auction.original_authority.as_ref(),
+                    &[auction.bump],
+                ],
+                program_id,
+            )
+            .map_err(|_| AuctionHouseError::InvalidBatchAccounts)?;
+            require!(
+                expected_auction == auction.key() && auction.auction_house == auction_house_key,
+                AuctionHouseError::InvalidBatchAccounts
+            );
+
+            if !auction.can_settle(now, ctx.accounts.auction_house.settlement_delay)? {
+                continue;
+            }
+
+            let optional = |info: &'info AccountInfo<'info>| {
+                if info.key == program_id {
+                    Ok(None)
+                } else {
+                    InterfaceAccount::<TokenAccount>::try_from(info).map(Some)
+                }
+            };
+            let token_mint = InterfaceAccount::<Mint>::try_from(&group[1])?;
+            let auction_nft_account = InterfaceAccount::<TokenAccount>::try_from(&group[2])?;
+            let owner_token_account = InterfaceAccount::<TokenAccount>::try_from(&group[3])?;
+            let auction_token_account = optional(&group[4])?;
+            let seller_proceeds_account = optional(&group[5])?;
+            let seller = (group[6].key != program_id).then(|| group[6].clone());
+            let metadata = &group[7];
+            let referrer_token_account = optional(&group[8])?;
+            let referrer = (group[9].key != program_id).then(|| group[9].clone());
+            let winner_token_account = optional(&group[10])?;
+            let winner = (group[11].key != program_id).then(|| group[11].clone());
+            let mut seller_index = if group[12].key == program_id {
+                None
+            } else {
+                let (expected_index, _) = Pubkey::find_program_address(
+                    &[
+                        b"seller_index",
+                        auction_house_key.as_ref(),
+                        auction.authority.as_ref(),
+                    ],
+                    program_id,
+                );
+                require!(
+                    group[12].key() == expected_index,
+                    AuctionHouseError::InvalidBatchAccounts
+                );
+                Some(Account::<SellerIndex>::try_from(&group[12])?)
+            };
+
+            // The same checks EndAuction applies through account constraints
+            let (expected_metadata, _) = Pubkey::find_program_address(
+                &[
+                    b"metadata",
+                    METADATA_PROGRAM_ID.as_ref(),
+                    auction.token_mint.as_ref(),
+                ],
+                &METADATA_PROGRAM_ID,
+            );
+            require!(
+                token_mint.key() == auction.token_mint
+                    && auction.treasury_mint == ctx.accounts.treasury_mint.key()
+                    && auction_nft_account.key() == auction.token_account
+                    && owner_token_account.owner == auction.authority
+                    && owner_token_account.mint == auction.token_mint
+                    && auction_token_account.as_ref().is_none_or(|account| {
+                        account.owner == auction.key() && account.mint == auction.treasury_mint
+                    })
+                    && seller_proceeds_account.as_ref().is_none_or(|account| {
+                        account.owner == auction.proceeds_destination
+                            && account.mint == auction.treasury_mint
+                    })
+                    && seller
+                        .as_ref()
+                        .is_none_or(|account| account.key() == auction.proceeds_destination)
+                    && referrer_token_account
+                        .as_ref()
+                        .is_none_or(|account| account.mint == auction.treasury_mint)
+                    && winner_token_account
+                        .as_ref()
+                        .is_none_or(|account| account.mint == auction.treasury_mint)
+                    && metadata.key() == expected_metadata,
+                AuctionHouseError::InvalidBatchAccounts
+            );
+
+            let accounts = SettlementAccounts {
+                auction_house: &ctx.accounts.auction_house,
+                auction_nft_account: &auction_nft_account,
+                owner_token_account: &owner_token_account,
+                auction_token_account: auction_token_account.as_ref(),
+                auction_house_treasury: &ctx.accounts.auction_house_treasury,
+                protocol_fee_account: ctx.accounts.protocol_fee_account.as_ref(),
+                seller_proceeds_account: seller_proceeds_account.as_ref(),
+                seller,
+                referrer_token_account: referrer_token_account.as_ref(),
+                referrer,
+                winner_token_account: winner_token_account.as_ref(),
+                winner,
+                metadata,
+                creator_accounts: &group[BATCH_SETTLEMENT_ACCOUNTS..],
+                token_mint: &token_mint,
+                treasury_mint: &ctx.accounts.treasury_mint,
+                token_program: &ctx.accounts.token_program,
+                treasury_token_program: &ctx.accounts.treasury_token_program,
+            };
+            settle_auction(&mut auction, &accounts, settler, now)?;
+            release_auction_slot(seller_index.as_mut(), &mut auction)?;
+
+            // Accounts loaded by hand are not written back by Anchor
+            auction.exit(program_id)?;
+            if let Some(seller_index) = seller_index {
+                seller_index.exit(program_id)?;
+            }
+            settled_count = settled_count
+                .checked_add(1)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        }
+
+        msg!("Batch ended {} auctions successfully", settled_count);
+        Ok(())
+    }
+
+    pub fn cancel_bid(ctx: Context<CancelBid>) -> Result<()> {
+        require!(
+            ctx.accounts.auction_house.allow_bid_retraction,
+            AuctionHouseError::BidRetractionNotAllowed
+        );
+
+        let auction = &mut ctx.accounts.auction;
+        let bid = &ctx.accounts.bid;
+        let now = Clock::get()?.unix_timestamp;
+        auction.check_retraction(&bid.bidder, now)?;
+
+        // Refund the full escrow, including any unused proxy ceiling and the bond
+        let amount = auction
+            .escrow_for(bid.amount)?
+            .checked_add(bid.bond)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        if amount > 0 {
+            withdraw_from_escrow(
+                auction,
+                ctx.accounts.auction_token_account.as_ref(),
+                ctx.accounts.bidder_token_account.as_ref(),
+                Some(ctx.accounts.bidder.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+                amount,
+            )?;
+        }
+
+        auction.reopen_after_retraction();
+
+        emit!(BidCancelled {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount,
+            timestamp: now,
+        });
+
+        // Anchor closes the bid account and returns its rent to the bidder
+        msg!("Bid cancelled successfully");
+        Ok(())
+    }
+
+    pub fn create_listing(
+        ctx: Context<CreateListing>,
+        listing_bump: u8,
+        token_size: u64,
+        price: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        // Curated houses must co-sign every listing
+        if ctx.accounts.auction_house.requires_sign_off {
+            let signed_off = ctx
+                .accounts
+                .auction_house_authority
+                .as_ref()
+                .is_some_and(|signer| signer.key() == ctx.accounts.auction_house.authority);
+            require!(signed_off, AuctionHouseError::AuctionHouseNotSignedOff);
+        }
+
+        require!(price > 0, AuctionHouseError::InvalidAuctionParameters);
+
+        // Move the NFT into the listing-owned escrow until it sells or is cancelled
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.token_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.listing_nft_account.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+
+        token_interface::transfer_checked(
+            transfer_ctx,
+            token_size,
+            ctx.accounts.token_mint.decimals,
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let listing = &mut ctx.accounts.listing;
+        listing.authority = ctx.accounts.authority.key();
+        listing.auction_house = ctx.accounts.auction_house.key();
+        listing.token_mint = ctx.accounts.token_mint.key();
+        listing.token_account = ctx.accounts.listing_nft_account.key();
+        listing.treasury_mint = ctx.accounts.treasury_mint.key();
+        listing.token_size = token_size;
+        listing.price = price;
+        listing.created_at = now;
+        listing.bump = listing_bump;
+
+        emit!(ListingCreated {
+            listing: listing.key(),
+            seller: listing.authority,
+            price,
+            timestamp: now,
+        });
+
+        msg!("Listing created successfully");
+        Ok(())
+    }
+
+    pub fn execute_sale(ctx: Context<ExecuteSale>, max_price: u64) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        let listing = &ctx.accounts.listing;
+        let native = listing.is_native();
+
+        // Revert rather than pay more than the buyer signed for if the price moved
+        require!(
+            listing.price <= max_price,
+            AuctionHouseError::PriceExceededMax
+        );
+
+        // The buyer pays the fees and the seller directly; nothing is escrowed
+        let (fee, protocol_fee, _, proceeds) = ctx.accounts.auction_house.split_sale(
+            listing.price,
+            ctx.accounts.auction_house.seller_fee_basis_points,
+            0,
+        )?;
+
+        if fee > 0 {
+            pay_from_signer(
+                native,
+                &ctx.accounts.buyer,
+                ctx.accounts.buyer_token_account.as_ref(),
+                Some(&ctx.accounts.auction_house_treasury),
+                Some(ctx.accounts.auction_house_treasury.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.treasury_token_program,
+                &ctx.accounts.system_program,
+                fee,
+            )?;
+
+            // A native SOL house keeps its treasury in wrapped SOL, so sync the new lamports
+            if native {
+                sync_wrapped_sol(
+                    &ctx.accounts.treasury_token_program,
+                    &ctx.accounts.auction_house_treasury,
+                )?;
+            }
+
+            emit!(FeePaid {
+                sale: listing.key(),
+                kind: FeeKind::House,
+                recipient: ctx.accounts.auction_house_treasury.key(),
+                amount: fee,
+            });
+        }
+        if protocol_fee > 0 {
+            let protocol_fee_account = ctx
+                .accounts
+                .protocol_fee_account
+                .as_ref()
+                .ok_or(AuctionHouseError::MissingPaymentAccount)?;
+            pay_from_signer(
+                native,
+                &ctx.accounts.buyer,
+                ctx.accounts.buyer_token_account.as_ref(),
+                Some(protocol_fee_account),
+                Some(protocol_fee_account.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.treasury_token_program,
+                &ctx.accounts.system_program,
+                protocol_fee,
+            )?;
+            if native {
+                sync_wrapped_sol(&ctx.accounts.treasury_token_program, protocol_fee_account)?;
+            }
+
+            emit!(FeePaid {
+                sale: listing.key(),
+                kind: FeeKind::Protocol,
+                recipient: protocol_fee_account.key(),
+                amount: protocol_fee,
+            });
+        }
+
+        pay_from_signer(
+            native,
+            &ctx.accounts.buyer,
+            ctx.accounts.buyer_token_account.as_ref(),
+            ctx.accounts.seller_proceeds_account.as_ref(),
+            Some(ctx.accounts.seller.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            &ctx.accounts.system_program,
+            proceeds,
+        )?;
+
+        // Deliver the NFT and close its escrow; Anchor closes the listing itself
+        transfer_from_listing(
+            listing,
+            &ctx.accounts.listing_nft_account,
+            &ctx.accounts.buyer_nft_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            listing.token_size,
+        )?;
+        close_listing_escrow(
+            listing,
+            &ctx.accounts.listing_nft_account,
+            ctx.accounts.seller.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+
+        emit!(ListingSold {
+            listing: listing.key(),
+            seller: listing.authority,
+            buyer: ctx.accounts.buyer.key(),
+            price: listing.price,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Listing sold successfully");
+        Ok(())
+    }
+
+    pub fn cancel_listing(ctx: Context<CancelListing>) -> Result<()> {
+        let listing = &ctx.accounts.listing;
+
+        // Only listing authority can cancel
+        require!(
+            listing.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // Return NFT to original owner
+        transfer_from_listing(
+            listing,
+            &ctx.accounts.listing_nft_account,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.token_program,
+            listing.token_size,
+        )?;
+        close_listing_escrow(
+            listing,
+            &ctx.accounts.listing_nft_account,
+            ctx.accounts.authority.to_account_info(),
+            &ctx.accounts.token_program,
+        )?;
+
+        emit!(ListingCancelled {
+            listing: listing.key(),
+            seller: listing.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Listing cancelled successfully");
+        Ok(())
+    }
+
+    pub fn make_offer(
+        ctx: Context<MakeOffer>,
+        offer_bump: u8,
+        token_size: u64,
+        amount: u64,
+        offer_expiry: i64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            amount > 0 && token_size > 0,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+        require!(offer_expiry > now, AuctionHouseError::OfferExpired);
+
+        let offer = &mut ctx.accounts.offer;
+        offer.offerer = ctx.accounts.offerer.key();
+        offer.auction_house = ctx.accounts.auction_house.key();
+        offer.token_mint = ctx.accounts.token_mint.key();
+        offer.treasury_mint = ctx.accounts.treasury_mint.key();
+        offer.owner = ctx.accounts.owner.key();
+        offer.token_size = token_size;
+        offer.amount = amount;
+        offer.offer_expiry = offer_expiry;
+        offer.created_at = now;
+        offer.bump = offer_bump;
+
+        // Escrow the offered amount: lamports in the offer account for native SOL,
+        // otherwise tokens in the offer-owned escrow
+        pay_from_signer(
+            offer.is_native(),
+            &ctx.accounts.offerer,
+            ctx.accounts.offerer_token_account.as_ref(),
+            ctx.accounts.offer_escrow.as_ref(),
+            Some(offer.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+
+        emit!(OfferMade {
+            offer: offer.key(),
+            offerer: offer.offerer,
+            owner: offer.owner,
+            token_mint: offer.token_mint,
+            amount,
+            offer_expiry,
+        });
+
+        msg!("Offer made successfully");
+        Ok(())
+    }
+
+    pub fn accept_offer(ctx: Context<AcceptOffer>) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        let offer = &ctx.accounts.offer;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Only the owner the offer was made to can accept, and only before it expires
+        require!(
+            offer.owner == ctx.accounts.owner.key(),
+            AuctionHouseError::Unauthorized
+        );
+        require!(now < offer.offer_expiry, AuctionHouseError::OfferExpired);
+
+        // The owner signs, so the NFT moves without passing through escrow
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.owner_nft_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.offerer_nft_account.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+
+        token_interface::transfer_checked(
+            transfer_ctx,
+            offer.token_size,
+            ctx.accounts.token_mint.decimals,
+        )?;
+
+        // Pay the fees to the treasury and protocol, and the rest to the owner
+        let (fee, protocol_fee, _, proceeds) = ctx.accounts.auction_house.split_sale(
+            offer.amount,
+            ctx.accounts.auction_house.seller_fee_basis_points,
+            0,
+        )?;
+
+        if fee > 0 {
+            withdraw_from_offer(
+                offer,
+                ctx.accounts.offer_escrow.as_ref(),
+                Some(&ctx.accounts.auction_house_treasury),
+                Some(ctx.accounts.auction_house_treasury.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.treasury_token_program,
+                fee,
+            )?;
+
+            // A native SOL house keeps its treasury in wrapped SOL, so sync the new lamports
+            if offer.is_native() {
+                sync_wrapped_sol(
+                    &ctx.accounts.treasury_token_program,
+                    &ctx.accounts.auction_house_treasury,
+                )?;
+            }
+
+            emit!(FeePaid {
+                sale: offer.key(),
+                kind: FeeKind::House,
+                recipient: ctx.accounts.auction_house_treasury.key(),
+                amount: fee,
+            });
+        }
+        if protocol_fee > 0 {
+            let protocol_fee_account = ctx
+                .accounts
+                .protocol_fee_account
+                .as_ref()
+                .ok_or(AuctionHouseError::MissingPaymentAccount)?;
+            withdraw_from_offer(
+                offer,
+                ctx.accounts.offer_escrow.as_ref(),
+                Some(protocol_fee_account),
+                Some(protocol_fee_account.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.treasury_token_program,
+                protocol_fee,
+            )?;
+            if offer.is_native() {
+                sync_wrapped_sol(&ctx.accounts.treasury_token_program, protocol_fee_account)?;
+            }
+
+            emit!(FeePaid {
+                sale: offer.key(),
+                kind: FeeKind::Protocol,
+                recipient: protocol_fee_account.key(),
+                amount: protocol_fee,
+            });
+        }
+
+        withdraw_from_offer(
+            offer,
+            ctx.accounts.offer_escrow.as_ref(),
+            ctx.accounts.seller_proceeds_account.as_ref(),
+            Some(ctx.accounts.owner.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.treasury_token_program,
+            proceeds,
+        )?;
+
+        emit!(OfferAccepted {
+            offer: offer.key(),
+            offerer: offer.offerer,
+            owner: offer.owner,
+            amount: offer.amount,
+            timestamp: now,
+        });
+
+        // Anchor closes the offer and returns its rent to the offerer
+        msg!("Offer accepted successfully");
+        Ok(())
+    }
+
+    pub fn cancel_offer(ctx: Context<CancelOffer>) -> Result<()> {
+        let offer = &ctx.accounts.offer;
+
+        // Only the offerer can reclaim an offer, whether or not it has expired
+        require!(
+            offer.offerer == ctx.accounts.offerer.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        withdraw_from_offer(
+            offer,
+            ctx.accounts.offer_escrow.as_ref(),
+            ctx.accounts.offerer_token_account.as_ref(),
+            Some(ctx.accounts.offerer.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            offer.amount,
+        )?;
+
+        emit!(OfferCancelled {
+            offer: offer.key(),
+            offerer: offer.offerer,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        // Anchor closes the offer and returns its rent to the offerer
+        msg!("Offer cancelled successfully");
+        Ok(())
+    }
+
+    pub fn place_penny_bid(ctx: Context<PlacePennyBid>, proof: Vec<[u8; 32]>) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        ctx.accounts
+            .auction
+            .admission(&ctx.accounts.bidder.key(), &proof)
+            .require_accepted()?;
+
+        let auction = &mut ctx.accounts.auction;
+        let bid = &mut ctx.accounts.bid;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Scheduled auctions open for bidding at start_time
+        auction.activate_if_started(now);
+        require!(
+            auction.status != AuctionStatus::Pending,
+            AuctionHouseError::AuctionNotStarted
+        );
+
+        // Check if auction is still active
+        require!(
+            auction.is_active(),
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Check if auction has ended
+        require!(
+            now < auction.end_time,
+            AuctionHouseError::AuctionEnded
+        );
+
+        require!(
+            auction.auction_type == AuctionType::PennyAuction as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // Throttle bids that follow the previous one too closely, whoever placed it
+        require!(
+            auction.bid_interval_elapsed(now)?,
+            AuctionHouseError::BiddingTooFast
+        );
+
+        // Sellers may not bid up their own listing unless the house allows it
+        let bidder_key = ctx.accounts.bidder.key();
+        require!(
+            ctx.accounts.auction_house.allow_self_bid || bidder_key != auction.authority,
+            AuctionHouseError::SelfBiddingNotAllowed
+        );
+
+        // Every bid raises the price by exactly one step
+        let price = auction
+            .current_price
+            .checked_add(auction.min_bid_increment)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        require!(
+            price <= ctx.accounts.auction_house.absolute_max_bid,
+            AuctionHouseError::BidExceedsCeiling
+        );
+        require!(
+            price >= auction.min_bid_raw,
+            AuctionHouseError::BidBelowMinimumUnit
+        );
+
+        // The bid fee goes straight to the treasury and is not refunded, win or lose
+        if auction.bid_fee > 0 {
+            pay_from_signer(
+                auction.is_native(),
+                &ctx.accounts.bidder,
+                ctx.accounts.bidder_token_account.as_ref(),
+                Some(&ctx.accounts.auction_house_treasury),
+                Some(ctx.accounts.auction_house_treasury.to_account_info()),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+                &ctx.accounts.system_program,
+                auction.bid_fee,
+            )?;
+
+            // A native SOL house keeps its treasury in wrapped SOL, so sync the new lamports
+            if auction.is_native() {
+                sync_wrapped_sol(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.auction_house_treasury,
+                )?;
+            }
+
+            emit!(FeePaid {
+                sale: auction.key(),
+                kind: FeeKind::BidFee,
+                recipient: ctx.accounts.auction_house_treasury.key(),
+                amount: auction.bid_fee,
+            });
+        }
+
+        // A record left by an earlier auction at this address holds nothing here
+        if auction.is_stale_bid(bid) {
+            bid.clear_stale();
+        }
+
+        // The leader keeps the new price escrowed, so a repeat bid only tops up the step
+        let top_up = price
+            .checked_sub(bid.amount)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        deposit_to_escrow(
+            auction,
+            &ctx.accounts.bidder,
+            ctx.accounts.bidder_token_account.as_ref(),
+            ctx.accounts.auction_token_account.as_ref(),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            top_up,
+        )?;
+
+        // Refund the previous highest bidder now that they have been outbid
+        if auction.highest_bidder == Some(bidder_key) {
+            // Raising your own bid must not pass the same bid account twice
+            require!(
+                ctx.accounts.previous_bid.is_none(),
+                AuctionHouseError::InvalidPreviousBidder
+            );
+        } else {
+            let outbid = auction.highest_bidder;
+            refund_highest_bidder(
+                auction,
+                ctx.accounts.previous_bid.as_mut(),
+                ctx.accounts.previous_bidder_token_account.as_ref(),
+                ctx.accounts
+                    .previous_bidder
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                ctx.accounts.auction_token_account.as_ref(),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+            )?;
+            if let (Some(outbid), Some(notify_program)) = (outbid, &ctx.accounts.notify_program) {
+                notify_outbid(notify_program, &auction.key(), &outbid, price)?;
+            }
+        }
+
+        let bid_nonce = auction
+            .bid_count
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        // Update auction state; every bid pushes the clock back, up to max_end_time
+        auction.current_price = price;
+        auction.highest_bidder = Some(bidder_key);
+        auction.bid_count = bid_nonce;
+        auction.last_bid_time = now;
+        auction.end_time = auction.extended_end_time()?;
+        auction.record_bid(bidder_key, price, now);
+
+        // Create or update bid record
+        bid.auction = auction.key();
+        bid.bidder = bidder_key;
+        bid.amount = price;
+        bid.max_bid = price;
+        bid.timestamp = now;
+        bid.bid_nonce = bid_nonce;
+
+        emit!(BidPlaced {
+            auction: auction.key(),
+            bidder: bidder_key,
+            amount: price,
+            bid_nonce,
+            timestamp: now,
+        });
+
+        msg!("Penny bid placed successfully");
+        Ok(())
+    }
+
+    pub fn deposit_bidder_escrow(
+        ctx: Context<DepositBidderEscrow>,
+        escrow_bump: u8,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        let bidder_escrow = &mut ctx.accounts.bidder_escrow;
+        bidder_escrow.auction_house = ctx.accounts.auction_house.key();
+        bidder_escrow.bidder = ctx.accounts.bidder.key();
+        bidder_escrow.treasury_mint = ctx.accounts.treasury_mint.key();
+        bidder_escrow.bump = escrow_bump;
+
+        // Lamports are held in the escrow account itself for native SOL houses
+        pay_from_signer(
+            bidder_escrow.is_native(),
+            &ctx.accounts.bidder,
+            ctx.accounts.bidder_token_account.as_ref(),
+            ctx.accounts.bidder_escrow_token_account.as_ref(),
+            Some(bidder_escrow.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        bidder_escrow.available = bidder_escrow
+            .available
+            .checked_add(amount)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        msg!("Bidder escrow funded successfully");
+        Ok(())
+    }
+
+    pub fn withdraw_bidder_escrow(ctx: Context<WithdrawBidderEscrow>, amount: u64) -> Result<()> {
+        let bidder_escrow = &mut ctx.accounts.bidder_escrow;
+
+        // Only the unspent balance can leave; funds already bid are in auction escrows
+        bidder_escrow.available = bidder_escrow
+            .available
+            .checked_sub(amount)
+            .ok_or(AuctionHouseError::InsufficientFunds)?;
+
+        withdraw_from_bidder_escrow(
+            bidder_escrow,
+            ctx.accounts.bidder_escrow_token_account.as_ref(),
+            ctx.accounts.bidder_token_account.as_ref(),
+            Some(ctx.accounts.bidder.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            amount,
+        )?;
+
+        msg!("Bidder escrow withdrawn successfully");
+        Ok(())
+    }
+
+    /// Assigns a bidder the share of the house fee rebated to them on auctions they
+    /// win. Zero removes the rebate. The rate is recorded on the auction when the
+    /// bidder takes the lead, so later changes only apply to new leading bids.
+    pub fn set_loyalty_tier(
+        ctx: Context<SetLoyaltyTier>,
+        loyalty_bump: u8,
+        rebate_basis_points: u16,
+    ) -> Result<()> {
+        // Only auction house authority can assign loyalty tiers
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            rebate_basis_points <= 10000,
+            AuctionHouseError::InvalidRebateBasisPoints
+        );
+
+        let loyalty = &mut ctx.accounts.loyalty;
+        loyalty.auction_house = ctx.accounts.auction_house.key();
+        loyalty.bidder = ctx.accounts.bidder.key();
+        loyalty.rebate_basis_points = rebate_basis_points;
+        loyalty.bump = loyalty_bump;
+
+        msg!("Loyalty tier set successfully");
+        Ok(())
+    }
+
+    /// Pushes an open auction's end_time later, within the house's maximum duration
+    pub fn extend_auction(ctx: Context<ExtendAuction>, new_end_time: i64) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let auction_house = &ctx.accounts.auction_house;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Only auction authority can extend
+        require!(
+            auction.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // Check if auction is still taking bids
+        auction.activate_if_started(now);
+        require!(
+            auction.is_active() && now < auction.end_time,
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Dutch prices and sealed reveal windows are scheduled against the original end
+        require!(
+            auction.auction_type != AuctionType::DutchDescending as u8
+                && auction.auction_type != AuctionType::SealedSecondPrice as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        auction.check_extension(new_end_time, auction_house.max_auction_duration)?;
+
+        let old_end_time = auction.end_time;
+        auction.end_time = new_end_time;
+
+        emit!(AuctionExtended {
+            auction: auction.key(),
+            old_end_time,
+            new_end_time,
+            timestamp: now,
+        });
+
+        msg!("Auction extended successfully");
+        Ok(())
+    }
+
+    /// Auctions token_mint together with further NFTs as one lot. remaining_accounts
+    /// holds a (mint, metadata, seller token account, escrow) group per bundled NFT,
+    /// where each escrow is a token account owned by the auction PDA. Bundled NFTs are
+    /// delivered by claim_nft, or returned by cancel_auction.
+    pub fn create_bundle_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CreateAuction<'info>>,
+        auction_bump: u8,
+        args: CreateAuctionArgs,
+    ) -> Result<()> {
+        let groups = ctx.remaining_accounts.chunks(4);
+        require!(
+            ctx.remaining_accounts.len().is_multiple_of(4)
+                && (1..=MAX_BUNDLE_MINTS).contains(&groups.len()),
+            AuctionHouseError::InvalidBundleAccounts
+        );
+
+        // A bundle is a lot of single NFTs, each leaving escrow by a plain transfer
+        require!(!args.multi_unit, AuctionHouseError::InvalidAuctionParameters);
+        let lead = read_metadata_asset(&ctx.accounts.metadata, &ctx.accounts.token_mint.key())?;
+        require!(
+            !lead.is_some_and(|asset| asset.is_programmable()),
+            AuctionHouseError::ProgrammableBundle
+        );
+
+        let auction_key = ctx.accounts.auction.key();
+        let allowed_collection = ctx.accounts.auction_house.allowed_collection;
+        let mut bundle: Vec<Pubkey> = Vec::with_capacity(groups.len());
+        for group in groups {
+            let mint = InterfaceAccount::<Mint>::try_from(&group[0])?;
+            let metadata = &group[1];
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(&group[2])?;
+            let escrow = InterfaceAccount::<TokenAccount>::try_from(&group[3])?;
+
+            // Each mint appears once in the lot, and only as a one-of-one NFT
+            require!(
+                mint.key() != ctx.accounts.token_mint.key() && !bundle.contains(&mint.key()),
+                AuctionHouseError::InvalidBundleAccounts
+            );
+            require!(
+                mint.decimals == 0 && mint.supply == 1,
+                AuctionHouseError::NotAnNft
+            );
+
+            // Validate the escrow the same way create_auction's accounts are checked
+            let (expected_metadata, _) = Pubkey::find_program_address(
+                &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.key().as_ref()],
+                &METADATA_PROGRAM_ID,
+            );
+            require!(
+                metadata.key() == expected_metadata
+                    && token_account.owner == ctx.accounts.authority.key()
+                    && token_account.mint == mint.key()
+                    && escrow.owner == auction_key
+                    && escrow.mint == mint.key(),
+                AuctionHouseError::InvalidBundleAccounts
+            );
+
+            let asset = read_metadata_asset(metadata, &mint.key())?;
+            require!(
+                !asset.as_ref().is_some_and(|asset| asset.is_programmable()),
+                AuctionHouseError::ProgrammableBundle
+            );
+            if let Some(allowed_collection) = allowed_collection {
+                let in_collection = asset
+                    .as_ref()
+                    .and_then(|asset| asset.collection.as_ref())
+                    .is_some_and(|collection| {
+                        collection.verified && collection.key == allowed_collection
+                    });
+                require!(in_collection, AuctionHouseError::CollectionNotAllowed);
+            }
+            require!(
+                is_transferable(&token_account, false),
+                AuctionHouseError::TokenAccountNotTransferable
+            );
+
+            let transfer_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: token_account.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: escrow.to_account_info(),
+                    authority: ctx.accounts.authority.to_account_info(),
+                },
+            );
+            token_interface::transfer_checked(transfer_ctx, 1, mint.decimals)?;
+
+            bundle.push(mint.key());
+        }
+        ctx.accounts.auction.bundle = bundle;
+
+        // Everything else, token_mint included, is set up exactly like a single auction
+        create_auction(ctx, auction_bump, args)
+    }
+
+    /// Last-resort recovery of an NFT left in escrow long after its auction closed.
+    /// A settled sale's NFT goes to the winner and anything else back to the seller.
+    /// remaining_accounts takes the same programmable or bundle accounts as claim_nft.
+    pub fn emergency_withdraw<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EmergencyWithdraw<'info>>,
+    ) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Only auction house authority can recover stranded NFTs
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        let unlocks_at = auction
+            .closes_at()
+            .checked_add(EMERGENCY_WITHDRAW_TIMELOCK)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        require!(now >= unlocks_at, AuctionHouseError::EmergencyTimelockActive);
+
+        require!(!auction.claimed, AuctionHouseError::NftAlreadyClaimed);
+
+        // Nothing may be recovered while a bidder could still be owed their escrow
+        require!(
+            !escrow_holds_funds(auction, ctx.accounts.auction_token_account.as_ref())?,
+            AuctionHouseError::BidderFundsRemaining
+        );
+
+        let sold = auction.settled
+            && auction.status == AuctionStatus::Ended
+            && auction.highest_bidder.is_some();
+        let recipient = auction
+            .highest_bidder
+            .filter(|_| sold)
+            .unwrap_or(auction.authority);
+        require!(
+            ctx.accounts.recipient.key() == recipient
+                && ctx.accounts.recipient_token_account.owner == recipient,
+            AuctionHouseError::Unauthorized
+        );
+
+        // An auction that never settled is closed out so no other path touches it
+        if !auction.is_final() {
+            auction.status = AuctionStatus::Cancelled;
+            auction.settled = true;
+        }
+        auction.claimed = true;
+
+        if auction.programmable {
+            let auction_seeds = &[
+                b"auction".as_ref(),
+                auction.token_mint.as_ref(),
+                auction.original_authority.as_ref(),
+                &[auction.bump],
+            ];
+            transfer_programmable(
+                ctx.remaining_accounts,
+                &ProgrammableTransfer {
+                    source: ctx.accounts.auction_nft_account.to_account_info(),
+                    source_owner: auction.to_account_info(),
+                    destination: ctx.accounts.recipient_token_account.to_account_info(),
+                    destination_owner: ctx.accounts.recipient.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    authority: auction.to_account_info(),
+                    payer: ctx.accounts.authority.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                auction.token_size,
+                &[&auction_seeds[..]],
+            )?;
+        } else {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.recipient_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            transfer_bundle(
+                auction,
+                ctx.remaining_accounts,
+                &recipient,
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        emit!(EmergencyWithdrawal {
+            auction: auction.key(),
+            recipient,
+            authority: ctx.accounts.authority.key(),
+            timestamp: now,
+        });
+
+        msg!("Emergency withdrawal successful");
+        Ok(())
+    }
+
+    pub fn freeze_auction(ctx: Context<FreezeAuction>) -> Result<()> {
+        // Only auction house authority can freeze auctions
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        ctx.accounts.auction.frozen = true;
+
+        msg!("Auction frozen successfully");
+        Ok(())
+    }
+
+    pub fn unfreeze_auction(ctx: Context<FreezeAuction>) -> Result<()> {
+        // Only auction house authority can unfreeze auctions
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        ctx.accounts.auction.frozen = false;
+
+        msg!("Auction unfrozen successfully");
+        Ok(())
+    }
+
+    /// Settles a dispute over a frozen auction. CompleteSale settles it as
+    /// end_auction would once its settlement window has passed; RefundAndReturn
+    /// refunds the winner and returns the NFT to the seller. Either way the auction
+    /// is unfrozen, and outbid bidders and bonds withdraw through withdraw_bid.
+    pub fn resolve_disputed_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ResolveDisputedAuction<'info>>,
+        resolution: Resolution,
+    ) -> Result<()> {
+        // Only auction house authority can resolve disputes
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // Only an auction frozen before settlement is in dispute
+        require!(
+            ctx.accounts.auction.frozen,
+            AuctionHouseError::AuctionNotDisputed
+        );
+        require!(
+            !ctx.accounts.auction.settled,
+            AuctionHouseError::AuctionAlreadySettled
+        );
+
+        let authority = ctx.accounts.authority.key();
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.auction.frozen = false;
+
+        match resolution {
+            Resolution::CompleteSale => {
+                let accounts = SettlementAccounts {
+                    auction_house: &ctx.accounts.auction_house,
+                    auction_nft_account: &ctx.accounts.auction_nft_account,
+                    owner_token_account: &ctx.accounts.owner_token_account,
+                    auction_token_account: ctx.accounts.auction_token_account.as_ref(),
+                    auction_house_treasury: &ctx.accounts.auction_house_treasury,
+                    protocol_fee_account: ctx.accounts.protocol_fee_account.as_ref(),
+                    seller_proceeds_account: ctx.accounts.seller_proceeds_account.as_ref(),
+                    seller: ctx
+                        .accounts
+                        .seller
+                        .as_ref()
+                        .map(|account| account.to_account_info()),
+                    referrer_token_account: ctx.accounts.referrer_token_account.as_ref(),
+                    referrer: ctx
+                        .accounts
+                        .referrer
+                        .as_ref()
+                        .map(|account| account.to_account_info()),
+                    winner_token_account: ctx.accounts.winner_token_account.as_ref(),
+                    winner: ctx
+                        .accounts
+                        .winner
+                        .as_ref()
+                        .map(|account| account.to_account_info()),
+                    metadata: &ctx.accounts.metadata,
+                    creator_accounts: ctx.remaining_accounts,
+                    token_mint: &ctx.accounts.token_mint,
+                    treasury_mint: &ctx.accounts.treasury_mint,
+                    token_program: &ctx.accounts.token_program,
+                    treasury_token_program: &ctx.accounts.treasury_token_program,
+                };
+
+                settle_auction(&mut ctx.accounts.auction, &accounts, authority, now)?;
+            }
+            Resolution::RefundAndReturn => {
+                let auction = &mut ctx.accounts.auction;
+
+                // The winner gets back their whole bid; any bond stays on the bid
+                // record for withdraw_bid
+                refund_highest_bidder(
+                    auction,
+                    ctx.accounts.winner_bid.as_mut(),
+                    ctx.accounts.winner_token_account.as_ref(),
+                    ctx.accounts
+                        .winner
+                        .as_ref()
+                        .map(|account| account.to_account_info()),
+                    ctx.accounts.auction_token_account.as_ref(),
+                    &ctx.accounts.treasury_mint,
+                    &ctx.accounts.treasury_token_program,
+                )?;
+
+                // The unwound sale is final like a cancellation, so no other path
+                // settles it
+                auction.status = AuctionStatus::Cancelled;
+                auction.settled = true;
+                auction.settled_by = Some(authority);
+
+                // Programmable NFTs and bundles are reclaimed with claim_nft
+                if !auction.held_until_claimed() {
+                    transfer_from_auction(
+                        auction,
+                        &ctx.accounts.auction_nft_account,
+                        &ctx.accounts.owner_token_account,
+                        &ctx.accounts.token_mint,
+                        &ctx.accounts.token_program,
+                        auction.token_size,
+                    )?;
+                }
+            }
+        }
+
+        let auction = &mut ctx.accounts.auction;
+        auction.resolution = Some(resolution);
+        release_auction_slot(ctx.accounts.seller_index.as_mut(), auction)?;
+
+        emit!(AuctionResolved {
+            auction: auction.key(),
+            resolution,
+            authority,
+            timestamp: now,
+        });
+
+        msg!("Auction dispute resolved successfully");
+        Ok(())
+    }
+
+    /// Read-only view of the smallest bid that place_bid accepts and that can win,
+    /// so clients do not have to combine price, increment and reserve themselves
+    pub fn get_minimum_next_bid(ctx: Context<GetAuctionInfo>) -> Result<u64> {
+        ctx.accounts.auction.minimum_winning_bid()
+    }
+
+    /// Reopens an English auction that closed without a sale, reusing the NFT already
+    /// in escrow. An unsettled high bid below the reserve is refunded here; after
+    /// settlement its bidder must have withdrawn before the auction can be relisted.
+    pub fn relist(ctx: Context<Relist>, args: RelistArgs) -> Result<()> {
+        require!(
+            !ctx.accounts.auction_house.paused,
+            AuctionHouseError::AuctionHousePaused
+        );
+
+        let auction = &mut ctx.accounts.auction;
+        let auction_house = &ctx.accounts.auction_house;
+        let now = Clock::get()?.unix_timestamp;
+
+        // Only auction authority can relist
+        require!(
+            auction.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            auction.auction_type == AuctionType::EnglishAscending as u8,
+            AuctionHouseError::WrongAuctionType
+        );
+
+        // Only auctions that closed unsold, settled or not, can be reopened
+        let unsold = match auction.effective_status(now) {
+            AuctionStatus::ReserveNotMet => true,
+            AuctionStatus::Ended => auction.highest_bidder.is_none(),
+            _ => false,
+        };
+        require!(unsold, AuctionHouseError::AuctionNotEnded);
+
+        // Settlement hands most unsold NFTs straight back to the seller
+        require!(
+            !auction.claimed && ctx.accounts.auction_nft_account.amount >= auction.token_size,
+            AuctionHouseError::NftNotInEscrow
+        );
+
+        if auction.settled {
+            require!(
+                !escrow_holds_funds(auction, ctx.accounts.auction_token_account.as_ref())?,
+                AuctionHouseError::BidderFundsRemaining
+            );
+        } else {
+            refund_highest_bidder(
+                auction,
+                ctx.accounts.previous_bid.as_mut(),
+                ctx.accounts.previous_bidder_token_account.as_ref(),
+                ctx.accounts
+                    .previous_bidder
+                    .as_ref()
+                    .map(|account| account.to_account_info()),
+                ctx.accounts.auction_token_account.as_ref(),
+                &ctx.accounts.treasury_mint,
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        let minimum_price = args.minimum_price.unwrap_or(auction.minimum_price);
+        let reserve_price = args.reserve_price.unwrap_or(auction.reserve_price);
+        require!(
+            minimum_price > 0 || reserve_price > 0,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+
+        // The new run is held to the same house duration limits as a new auction
+        let earliest_end = now
+            .checked_add(auction_house.min_auction_duration)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        require!(
+            args.end_time > earliest_end,
+            AuctionHouseError::InvalidAuctionDuration
+        );
+        if auction_house.max_auction_duration > 0 {
+            let latest_end = now
+                .checked_add(auction_house.max_auction_duration)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+            require!(
+                args.end_time < latest_end,
+                AuctionHouseError::InvalidAuctionDuration
+            );
+        }
+
+        let max_end_time = args.max_end_time.unwrap_or(0);
+        require!(
+            max_end_time == 0 || max_end_time >= args.end_time,
+            AuctionHouseError::InvalidExtensionSettings
+        );
+
+        auction.minimum_price = minimum_price;
+        auction.reserve_price = reserve_price;
+        auction.current_price = minimum_price;
+        auction.created_at = now;
+        auction.start_time = now;
+        auction.end_time = args.end_time;
+        auction.max_end_time = max_end_time;
+        auction.second_highest_bid = 0;
+        auction.highest_bidder = None;
+        auction.referrer = None;
+        auction.winner_rebate_basis_points = 0;
+        auction.settled = false;
+        auction.settled_by = None;
+        auction.settlement_verified = false;
+        auction.resolution = None;
+        auction.bid_count = 0;
+        auction.last_bid_time = 0;
+        auction.clear_bid_history();
+        auction.status = AuctionStatus::Active;
+
+        // An auction that gave up its slot once final takes a new one, within the
+        // house limit like any new listing
+        if !auction.holds_seller_slot {
+            ctx.accounts
+                .seller_index
+                .as_mut()
+                .ok_or(AuctionHouseError::MissingSellerIndex)?
+                .open_slot(auction, auction_house.max_active_auctions_per_seller)?;
+        }
+
+        emit!(AuctionCreated {
+            auction: auction.key(),
+            seller: auction.authority,
+            minimum_price,
+            timestamp: now,
+        });
+
+        msg!("Auction relisted successfully");
+        Ok(())
+    }
+
+    /// Lets auctions be priced in another treasury mint, with fees collected in a
+    /// treasury of that mint. Registering a removed mint again re-enables it and
+    /// updates its fee destinations.
+    pub fn add_treasury_mint(
+        ctx: Context<AddTreasuryMint>,
+        registered_bump: u8,
+        treasury_bump: u8,
+        fee_withdrawal_destination: Pubkey,
+        protocol_fee_destination: Pubkey,
+    ) -> Result<()> {
+        let auction_house = &ctx.accounts.auction_house;
+
+        // Only auction house authority can register treasury mints
+        require!(
+            auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            ctx.accounts.treasury_mint.key() != auction_house.treasury_mint,
+            AuctionHouseError::InvalidTreasuryMint
+        );
+
+        let registered_mint = &mut ctx.accounts.registered_mint;
+        registered_mint.auction_house = auction_house.key();
+        registered_mint.treasury_mint = ctx.accounts.treasury_mint.key();
+        registered_mint.treasury = ctx.accounts.auction_house_treasury.key();
+        registered_mint.fee_withdrawal_destination = fee_withdrawal_destination;
+        registered_mint.protocol_fee_destination = protocol_fee_destination;
+        registered_mint.enabled = true;
+        registered_mint.treasury_bump = treasury_bump;
+        registered_mint.bump = registered_bump;
+
+        msg!("Treasury mint added successfully");
+        Ok(())
+    }
+
+    pub fn remove_treasury_mint(ctx: Context<RemoveTreasuryMint>) -> Result<()> {
+        // Only auction house authority can remove treasury mints
+        require!(
+            ctx.accounts.auction_house.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        // The registration stays so auctions already priced in the mint can settle
+        ctx.accounts.registered_mint.enabled = false;
+
+        msg!("Treasury mint removed successfully");
+        Ok(())
+    }
+
+    /// Confirms an auction is fully wound down: it is over and everything paid into
+    /// its escrow has been paid back out. Permissionless, so operators and indexers
+    /// can rely on settlement_verified before closing out an auction.
+    pub fn verify_settlement(ctx: Context<VerifySettlement>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+
+        require!(auction.is_final(), AuctionHouseError::AuctionStillActive);
+
+        // Every payment into escrow must have been paid back out, and the escrow
+        // itself must be empty
+        require!(
+            auction.escrowed_total == auction.withdrawn_total
+                && !escrow_holds_funds(auction, ctx.accounts.auction_token_account.as_ref())?,
+            AuctionHouseError::SettlementIncomplete
+        );
+
+        auction.settlement_verified = true;
+
+        msg!("Settlement verified successfully");
+        Ok(())
+    }
+
+    /// Withdraws an auction nobody has bid on: returns the NFT and closes the auction
+    /// and its NFT escrow, refunding their rent to the seller in the same call.
+    /// Auctions with bids go through cancel_auction and bidder withdrawals instead.
+    pub fn abort_unbid_auction<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AbortUnbidAuction<'info>>,
+    ) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+
+        // Only auction authority can abort
+        require!(
+            auction.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            auction.is_open_or_pending(),
+            AuctionHouseError::AuctionNotActive
+        );
+
+        // Without a bid no one has funds in escrow, so nothing outlives the auction
+        require!(
+            auction.highest_bidder.is_none() && auction.bid_count == 0,
+            AuctionHouseError::AuctionHasBids
+        );
+
+        auction.status = AuctionStatus::Cancelled;
+
+        if auction.programmable {
+            let auction_seeds = &[
+                b"auction".as_ref(),
+                auction.token_mint.as_ref(),
+                auction.original_authority.as_ref(),
+                &[auction.bump],
+            ];
+            transfer_programmable(
+                ctx.remaining_accounts,
+                &ProgrammableTransfer {
+                    source: ctx.accounts.auction_nft_account.to_account_info(),
+                    source_owner: auction.to_account_info(),
+                    destination: ctx.accounts.owner_token_account.to_account_info(),
+                    destination_owner: ctx.accounts.authority.to_account_info(),
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    authority: auction.to_account_info(),
+                    payer: ctx.accounts.authority.to_account_info(),
+                    token_program: ctx.accounts.token_program.to_account_info(),
+                },
+                auction.token_size,
+                &[&auction_seeds[..]],
+            )?;
+        } else {
+            transfer_from_auction(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                &ctx.accounts.owner_token_account,
+                &ctx.accounts.token_mint,
+                &ctx.accounts.token_program,
+                auction.token_size,
+            )?;
+            transfer_bundle(
+                auction,
+                ctx.remaining_accounts,
+                &auction.authority,
+                &ctx.accounts.token_program,
+            )?;
+
+            // Programmable escrows stay frozen by the metadata program and cannot be
+            // closed here
+            close_auction_escrow(
+                auction,
+                &ctx.accounts.auction_nft_account,
+                ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.token_program,
+            )?;
+        }
+
+        emit!(AuctionCancelled {
+            auction: auction.key(),
+            seller: auction.authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        if let Some(seller_index) = ctx.accounts.seller_index.as_mut() {
+            seller_index.release_slot(auction);
+            seller_index.remove(&auction.key());
+        }
+
+        // Anchor closes the auction and returns its rent to the seller
+        msg!("Auction aborted successfully");
+        Ok(())
+    }
+
+    /// Forfeits the bid bond of a winner who has not claimed the NFT by the end of the
+    /// claim window, paying it to the seller. Anyone may call it; the NFT itself stays
+    /// claimable.
+    pub fn reclaim_bond(ctx: Context<ReclaimBond>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let bid = &mut ctx.accounts.bid;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(
+            auction.settled
+                && auction.status == AuctionStatus::Ended
+                && auction.highest_bidder == Some(bid.bidder)
+                && !auction.claimed
+                && bid.bond > 0,
+            AuctionHouseError::BondNotForfeitable
+        );
+        require!(
+            now >= auction.bond_forfeits_at(ctx.accounts.auction_house.settlement_delay)?,
+            AuctionHouseError::SettlementWindowNotElapsed
+        );
+
+        let bond = bid.bond;
+        bid.bond = 0;
+        withdraw_from_escrow(
+            auction,
+            ctx.accounts.auction_token_account.as_ref(),
+            ctx.accounts.seller_proceeds_account.as_ref(),
+            ctx.accounts
+                .seller
+                .as_ref()
+                .map(|account| account.to_account_info()),
+            &ctx.accounts.treasury_mint,
+            &ctx.accounts.token_program,
+            bond,
+        )?;
+
+        emit!(BondForfeited {
+            auction: auction.key(),
+            bidder: bid.bidder,
+            amount: bond,
+            timestamp: now,
+        });
+
+        msg!("Bid bond forfeited successfully");
+        Ok(())
+    }
+
+    /// Frees the active slot of a final auction that still holds one, such as a sale
+    /// settled before settlement freed slots itself. Permissionless, as it only ever
+    /// lowers the seller's count.
+    pub fn release_seller_slot(ctx: Context<ReleaseSellerSlot>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        require!(auction.is_final(), AuctionHouseError::AuctionStillActive);
+
+        ctx.accounts.seller_index.release_slot(auction);
+
+        msg!("Seller slot released successfully");
+        Ok(())
+    }
+
+    /// Read-only view of how many auctions count towards the seller's active limit
+    pub fn get_active_auction_count(ctx: Context<GetSellerIndex>) -> Result<u32> {
+        Ok(ctx.accounts.seller_index.active_auctions)
+    }
+
+    /// Hands an auction nobody has bid on to a new seller, who takes over its control
+    /// and proceeds. Bidders bid on terms set by the seller they saw, so the auction
+    /// cannot change hands once it has a bid. An active slot moves to the new
+    /// seller's index, counting against their limit instead.
+    pub fn transfer_auction_authority(ctx: Context<TransferAuctionAuthority>) -> Result<()> {
+        let auction = &mut ctx.accounts.auction;
+        let old_authority = ctx.accounts.authority.key();
+        let new_authority = ctx.accounts.new_authority.key();
+
+        // Only auction authority can transfer the auction
+        require!(
+            auction.authority == old_authority,
+            AuctionHouseError::Unauthorized
+        );
+
+        require!(
+            auction.is_open_or_pending(),
+            AuctionHouseError::AuctionNotActive
+        );
+        require!(
+            auction.highest_bidder.is_none() && auction.bid_count == 0,
+            AuctionHouseError::AuctionHasBids
+        );
+
+        if auction.holds_seller_slot {
+            let old_index = ctx
+                .accounts
+                .seller_index
+                .as_mut()
+                .ok_or(AuctionHouseError::MissingSellerIndex)?;
+            old_index.active_auctions = old_index.active_auctions.saturating_sub(1);
+            auction.holds_seller_slot = false;
+        }
+        if let Some(old_index) = ctx.accounts.seller_index.as_mut() {
+            old_index.remove(&auction.key());
+        }
+
+        let new_index = &mut ctx.accounts.new_seller_index;
+        new_index.open_slot(
+            auction,
+            ctx.accounts.auction_house.max_active_auctions_per_seller,
+        )?;
+        new_index.record(new_authority, auction.key())?;
+
+        auction.authority = new_authority;
+        auction.proceeds_destination = new_authority;
+
+        emit!(AuctionAuthorityTransferred {
+            auction: auction.key(),
+            old_authority,
+            new_authority,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+
+        msg!("Auction authority transferred successfully");
+        Ok(())
+    }
+
+    /// Closes finalized Bid and Auction accounts passed as remaining_accounts in pairs,
+    /// returning their rent to the payer who funded them: the bidder for a Bid, the
+    /// seller for an Auction. A Bid comes with its auction and an Auction with its NFT
+    /// escrow, which is closed along with it if still open. Nothing is closed while
+    /// its auction could still owe anyone escrow. A Bid whose auction is already
+    /// closed can always go, since the auction closes only once its escrow is empty.
+    /// A winner's Bid that outlives its auction still holds the paid-out amount, so
+    /// an auction later listed at the same address treats it as stale.
+    pub fn sweep_closed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SweepClosed<'info>>,
+    ) -> Result<()> {
+        let payer = ctx.accounts.payer.to_account_info();
+        require!(
+            !ctx.remaining_accounts.is_empty() && ctx.remaining_accounts.len().is_multiple_of(2),
+            AuctionHouseError::InvalidBatchAccounts
+        );
+
+        let lamports_before = payer.lamports();
+        let mut accounts_closed: u32 = 0;
+        for group in ctx.remaining_accounts.chunks_exact(2) {
+            if let Ok(bid) = Account::<Bid>::try_from(&group[0]) {
+                // Only the bidder funded the bid record
+                require!(
+                    bid.bidder == payer.key() && bid.auction == group[1].key(),
+                    AuctionHouseError::Unauthorized
+                );
+
+                // A closed auction was settlement verified first, so it owes no bid
+                if !group[1].data_is_empty() {
+                    let auction = Account::<Auction>::try_from(&group[1])?;
+                    require!(
+                        auction.bid_closeable(&bid)?,
+                        AuctionHouseError::AccountNotCloseable
+                    );
+                }
+
+                bid.close(payer.clone())?;
+            } else {
+                let auction = Account::<Auction>::try_from(&group[0])?;
+
+                // Only the seller who listed the auction funded its account, whoever
+                // holds it since transfer_auction_authority
+                require!(
+                    auction.original_authority == payer.key(),
+                    AuctionHouseError::Unauthorized
+                );
+                require!(auction.closeable(), AuctionHouseError::AccountNotCloseable);
+
+                // The NFT escrow must be empty; close it too unless that already happened
+                require!(
+                    group[1].key() == auction.token_account,
+                    AuctionHouseError::InvalidBatchAccounts
+                );
+                if group[1].lamports() > 0 {
+                    let escrow = InterfaceAccount::<TokenAccount>::try_from(&group[1])?;
+                    require!(
+                        escrow.amount == 0 && !auction.programmable,
+                        AuctionHouseError::AccountNotCloseable
+                    );
+                    close_auction_escrow(
+                        &auction,
+                        &escrow,
+                        payer.clone(),
+                        &ctx.accounts.token_program,
+                    )?;
+                }
+
+                auction.close(payer.clone())?;
+            }
+
+            accounts_closed = accounts_closed
+                .checked_add(1)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        }
+
+        emit!(RentReclaimed {
+            payer: payer.key(),
+            accounts_closed,
+            lamports: payer.lamports().saturating_sub(lamports_before),
+        });
+
+        msg!("Closed accounts swept successfully");
+        Ok(())
+    }
+
+    /// Brings an auction written under an older layout up to AUCTION_VERSION. The old
+    /// layout is recognised by its exact size and decoded field by field, the account
+    /// is reallocated to Auction::LEN with the payer topping up its rent, and every
+    /// field the old layout lacked takes its zero default. The auction is taken as raw
+    /// data because an old layout does not deserialize as Auction.
+    pub fn migrate_auction(ctx: Context<MigrateAuction>) -> Result<()> {
+        let auction_info = ctx.accounts.auction.to_account_info();
+        let authority = ctx.accounts.authority.key();
+
+        let (from_version, mut auction) = {
+            let data = auction_info.try_borrow_data()?;
+            require!(
+                auction_info.owner == ctx.program_id
+                    && data.len() >= 8
+                    && data[..8] == Auction::DISCRIMINATOR,
+                AuctionHouseError::InvalidAuctionAccount
+            );
+
+            if data.len() == AuctionV1::LEN {
+                let original = AuctionV1::deserialize(&mut &data[8..])
+                    .map_err(|_| AuctionHouseError::InvalidAuctionAccount)?;
+
+                // The original layout records no house, so only the seller can say
+                // which one the auction was listed on
+                let auction_house = ctx
+                    .accounts
+                    .auction_house
+                    .as_ref()
+                    .ok_or(AuctionHouseError::MissingAuctionHouse)?;
+                require!(
+                    authority == original.authority,
+                    AuctionHouseError::Unauthorized
+                );
+                require!(
+                    auction_house.treasury_mint == original.treasury_mint,
+                    AuctionHouseError::InvalidTreasuryMint
+                );
+                (1, original.upgrade(auction_house.key())?)
+            } else {
+                let version = Auction::VERSIONED_LENS
+                    .iter()
+                    .position(|len| *len == data.len())
+                    .map(|index| index as u8 + 2)
+                    .ok_or(AuctionHouseError::InvalidAuctionAccount)?;
+
+                // Versioned layouts only ever appended fields after version, so the
+                // zero-extended data reads them all as their zero defaults
+                let mut extended = data.to_vec();
+                extended.resize(Auction::LEN, 0);
+                let auction = Auction::try_deserialize(&mut &extended[..])?;
+                require!(
+                    auction.version == version,
+                    AuctionHouseError::InvalidAuctionAccount
+                );
+                require!(
+                    version < AUCTION_VERSION,
+                    AuctionHouseError::AuctionAlreadyMigrated
+                );
+
+                // Only the seller or their auction house's authority can migrate it
+                require!(
+                    authority == auction.authority
+                        || ctx.accounts.auction_house.as_ref().is_some_and(|house| {
+                            house.key() == auction.auction_house && house.authority == authority
+                        }),
+                    AuctionHouseError::Unauthorized
+                );
+                (version, auction)
+            }
+        };
+
+        // Auctions listed before proceeds_destination existed keep paying the seller
+        if from_version < 4 {
+            auction.proceeds_destination = auction.authority;
+        }
+        // Authority could not change before original_authority existed
+        if from_version < 6 {
+            auction.original_authority = auction.authority;
+        }
+        auction.version = AUCTION_VERSION;
+
+        grow_account(
+            &auction_info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            Auction::LEN,
+        )?;
+        write_account(&auction_info, &auction)?;
+
+        emit!(AuctionMigrated {
+            auction: auction_info.key(),
+            from_version,
+            to_version: AUCTION_VERSION,
+        });
+
+        msg!("Auction migrated successfully");
+        Ok(())
+    }
+
+    /// Brings an auction house written under the original layout up to the current
+    /// one. Every setting added since takes the value initialize_auction_house gives
+    /// it when left out: no limits or extra fees, seller-paid fees and no bid ceiling.
+    pub fn migrate_auction_house(ctx: Context<MigrateAuctionHouse>) -> Result<()> {
+        let auction_house_info = ctx.accounts.auction_house.to_account_info();
+
+        let original = {
+            let data = auction_house_info.try_borrow_data()?;
+            require!(
+                auction_house_info.owner == ctx.program_id
+                    && data.len() >= 8
+                    && data[..8] == AuctionHouse::DISCRIMINATOR,
+                AuctionHouseError::InvalidAuctionHouseAccount
+            );
+            require!(
+                data.len() != AuctionHouse::LEN,
+                AuctionHouseError::AuctionHouseAlreadyMigrated
+            );
+            require!(
+                data.len() == AuctionHouseV1::LEN,
+                AuctionHouseError::InvalidAuctionHouseAccount
+            );
+            AuctionHouseV1::deserialize(&mut &data[8..])
+                .map_err(|_| AuctionHouseError::InvalidAuctionHouseAccount)?
+        };
+
+        // Only the auction house authority can migrate it
+        require!(
+            original.authority == ctx.accounts.authority.key(),
+            AuctionHouseError::Unauthorized
+        );
+
+        let auction_house = original.upgrade()?;
+        grow_account(
+            &auction_house_info,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            AuctionHouse::LEN,
+        )?;
+        write_account(&auction_house_info, &auction_house)?;
+
+        emit!(AuctionHouseMigrated {
+            auction_house: auction_house_info.key(),
+        });
+
+        msg!("Auction house migrated successfully");
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(bump: u8, fee_payer_bump: u8, treasury_bump: u8)]
+pub struct InitializeAuctionHouse<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = AuctionHouse::LEN,
+        seeds = [b"auction_house", authority.key().as_ref()],
+        bump = bump
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = treasury_mint,
+        token::authority = auction_house,
+        token::token_program = token_program,
+        seeds = [b"auction_house_fee_account", auction_house.key().as_ref()],
+        bump = fee_payer_bump
+    )]
+    pub auction_house_fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = treasury_mint,
+        token::authority = auction_house,
+        token::token_program = token_program,
+        seeds = [b"auction_house_treasury", auction_house.key().as_ref()],
+        bump = treasury_bump
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: This is not dangerous because we don't read or write from this account
+    pub fee_withdrawal_destination: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_bump: u8)]
+pub struct CreateAuction<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Auction::LEN,
+        seeds = [b"auction", token_mint.key().as_ref(), authority.key().as_ref()],
+        bump = auction_bump
+    )]
+    pub auction: Account<'info, Auction>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of treasury_mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Metaplex metadata PDA of the NFT mint, parsed for its collection when the
+    /// house restricts listings to one
+    #[account(
+        seeds = [b"metadata", METADATA_PROGRAM_ID.as_ref(), token_mint.key().as_ref()],
+        bump,
+        seeds::program = METADATA_PROGRAM_ID
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = token_account.owner == authority.key(),
+        constraint = token_account.mint == token_mint.key()
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = token_mint,
+        token::authority = auction,
+        token::token_program = token_program,
+        seeds = [b"auction_nft", auction.key().as_ref()],
+        bump
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Lookup of the seller's auctions, created with their first listing
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SellerIndex::LEN,
+        seeds = [b"seller_index", auction_house.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerIndex>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Only required when the auction house has requires_sign_off set or the auction
+    /// carries a fee_override
+    pub auction_house_authority: Option<Signer<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(auction_bump: u8)]
+pub struct CreateAuctionWithAta<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Auction::LEN,
+        seeds = [b"auction", token_mint.key().as_ref(), authority.key().as_ref()],
+        bump = auction_bump
+    )]
+    pub auction: Account<'info, Auction>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of treasury_mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Metaplex metadata PDA of the NFT mint, parsed for its collection when the
+    /// house restricts listings to one
+    #[account(
+        seeds = [b"metadata", METADATA_PROGRAM_ID.as_ref(), token_mint.key().as_ref()],
+        bump,
+        seeds::program = METADATA_PROGRAM_ID
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        constraint = token_account.owner == authority.key(),
+        constraint = token_account.mint == token_mint.key()
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Associated token account of the auction PDA for token_mint, created here
+    /// unless tooling already derived and created it
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = token_mint,
+        associated_token::authority = auction,
+        associated_token::token_program = token_program
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Lookup of the seller's auctions, created with their first listing
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SellerIndex::LEN,
+        seeds = [b"seller_index", auction_house.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerIndex>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Only required when the auction house has requires_sign_off set or the auction
+    /// carries a fee_override
+    pub auction_house_authority: Option<Signer<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = Bid::LEN,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions, which escrow lamports in the auction account
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Bid record of the current highest bidder, required once the auction has a bid
+    #[account(mut)]
+    pub previous_bid: Option<Account<'info, Bid>>,
+    /// Treasury account of the current highest bidder, refunded in full when this bid
+    /// takes the lead. Not needed for native SOL auctions.
+    #[account(
+        mut,
+        constraint = previous_bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub previous_bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Wallet of the current highest bidder, refunded in native SOL auctions.
+    /// Checked against auction.highest_bidder before any lamports move.
+    #[account(mut)]
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Front-end wallet that sourced the bid. Only its key is recorded; it is
+    /// paid at settlement if this bid wins.
+    pub referrer: Option<UncheckedAccount<'info>>,
+    /// Bidder's loyalty tier, rebated part of the house fee if this bid wins
+    #[account(
+        constraint = loyalty.bidder == bidder.key(),
+        constraint = loyalty.auction_house == auction_house.key()
+    )]
+    pub loyalty: Option<Account<'info, LoyaltyAccount>>,
+    /// Prefunded balance to bid from instead of the bidder's wallet
+    #[account(
+        mut,
+        constraint = bidder_escrow.bidder == bidder.key(),
+        constraint = bidder_escrow.auction_house == auction_house.key()
+    )]
+    pub bidder_escrow: Option<Account<'info, BidderEscrow>>,
+    /// Escrow-owned treasury account, needed with bidder_escrow outside native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_escrow_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The house's notify_program, told when this bid outbids someone. If it
+    /// errors the whole bid fails, so it stays optional: leave it out to bid anyway.
+    #[account(
+        constraint = Some(notify_program.key()) == auction_house.notify_program
+    )]
+    pub notify_program: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBidWithSig<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        space = Bid::LEN,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions, which escrow lamports in the auction account
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Bid record of the current highest bidder, required once the auction has a bid
+    #[account(mut)]
+    pub previous_bid: Option<Account<'info, Bid>>,
+    /// Treasury account of the current highest bidder, refunded in full when this bid
+    /// takes the lead. Not needed for native SOL auctions.
+    #[account(
+        mut,
+        constraint = previous_bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub previous_bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Wallet of the current highest bidder, refunded in native SOL auctions.
+    /// Checked against auction.highest_bidder before any lamports move.
+    #[account(mut)]
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Front-end wallet that sourced the bid, part of the signed message so the
+    /// relayer cannot substitute its own. Only its key is recorded; it is paid at
+    /// settlement if this bid wins.
+    pub referrer: Option<UncheckedAccount<'info>>,
+    /// Bidder's loyalty tier, rebated part of the house fee if this bid wins
+    #[account(
+        constraint = loyalty.bidder == bidder.key(),
+        constraint = loyalty.auction_house == auction_house.key()
+    )]
+    pub loyalty: Option<Account<'info, LoyaltyAccount>>,
+    /// Prefunded balance the relayed bid is drawn from
+    #[account(
+        mut,
+        constraint = bidder_escrow.bidder == bidder.key(),
+        constraint = bidder_escrow.auction_house == auction_house.key()
+    )]
+    pub bidder_escrow: Account<'info, BidderEscrow>,
+    /// Escrow-owned treasury account, needed outside native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_escrow_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: The house's notify_program, told when this bid outbids someone. If it
+    /// errors the whole bid fails, so it stays optional: leave it out to bid anyway.
+    #[account(
+        constraint = Some(notify_program.key()) == auction_house.notify_program
+    )]
+    pub notify_program: Option<UncheckedAccount<'info>>,
+    /// CHECK: Bidder wallet, whose signature over the bid is verified against the
+    /// Ed25519 instruction before anything moves
+    pub bidder: UncheckedAccount<'info>,
+    /// Submits the bid and pays for the bid record
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+    /// CHECK: Instructions sysvar, read for the Ed25519 signature verification
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct EndAuction<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of the auction's treasury mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == auction.authority,
+        constraint = owner_token_account.mint == auction.token_mint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&auction.treasury_mint, registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = Some(protocol_fee_account.key())
+            == auction_house.protocol_fee_destination_for(
+                &auction.treasury_mint,
+                registered_mint.as_deref(),
+            )
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Receives treasury sweeps; only needed when the house sets a sweep threshold
+    #[account(
+        mut,
+        constraint = Some(fee_withdrawal_destination.key())
+            == auction_house.fee_withdrawal_destination_for(
+                &auction.treasury_mint,
+                registered_mint.as_deref(),
+            )
+    )]
+    pub fee_withdrawal_destination: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == auction.proceeds_destination,
+        constraint = seller_proceeds_account.mint == auction.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Proceeds destination wallet, only needed to receive proceeds in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = seller.key() == auction.proceeds_destination
+    )]
+    pub seller: Option<UncheckedAccount<'info>>,
+    /// Referrer's treasury mint token account, needed when the winning bid was referred
+    #[account(
+        mut,
+        constraint = referrer_token_account.mint == auction.treasury_mint
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Referrer wallet, only needed to receive the referral fee in native SOL
+    /// auctions. Checked against auction.referrer before any lamports move.
+    #[account(mut)]
+    pub referrer: Option<UncheckedAccount<'info>>,
+    /// Winner's treasury mint token account, needed when the winner has a loyalty rebate.
+    /// A bidless auction has no winner, so passing one fails instead of panicking.
+    #[account(
+        mut,
+        constraint = Some(winner_token_account.owner) == auction.highest_bidder,
+        constraint = winner_token_account.mint == auction.treasury_mint
+    )]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Winner wallet, only needed to receive a loyalty rebate in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = Some(winner.key()) == auction.highest_bidder
+    )]
+    pub winner: Option<UncheckedAccount<'info>>,
+    /// CHECK: Metaplex metadata PDA of the NFT mint, parsed for creator royalties.
+    /// Mints without metadata leave it uninitialized and pay no royalties.
+    #[account(
+        seeds = [b"metadata", METADATA_PROGRAM_ID.as_ref(), auction.token_mint.as_ref()],
+        bump,
+        seeds::program = METADATA_PROGRAM_ID
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// The seller's index, to free the auction's active slot; auctions created
+    /// before seller indexes existed have none
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), auction.authority.as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    pub settler: Signer<'info>,
+    /// Token program of the NFT mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFT's
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAuction<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == auction.authority,
+        constraint = owner_token_account.mint == auction.token_mint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// The seller's index, to free the auction's active slot; auctions created
+    /// before seller indexes existed have none
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        constraint = bid.auction == auction.key()
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct BuyNow<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key(),
+        constraint = buyer_token_account.mint == auction.treasury_mint
+    )]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Bid record of the current highest bidder, required once the auction has a bid
+    #[account(mut)]
+    pub previous_bid: Option<Account<'info, Bid>>,
+    #[account(
+        mut,
+        constraint = previous_bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub previous_bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Wallet of the current highest bidder, refunded in native SOL auctions.
+    /// Checked against auction.highest_bidder before any lamports move.
+    #[account(mut)]
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = buyer_nft_account.owner == buyer.key(),
+        constraint = buyer_nft_account.mint == auction.token_mint
+    )]
+    pub buyer_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of the auction's treasury mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&auction.treasury_mint, registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = Some(protocol_fee_account.key())
+            == auction_house.protocol_fee_destination_for(
+                &auction.treasury_mint,
+                registered_mint.as_deref(),
+            )
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == auction.proceeds_destination,
+        constraint = seller_proceeds_account.mint == auction.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Proceeds destination wallet, only needed to receive proceeds in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = seller.key() == auction.proceeds_destination
+    )]
+    pub seller: Option<UncheckedAccount<'info>>,
+    /// CHECK: Metaplex metadata PDA of the NFT mint, parsed for creator royalties.
+    /// Mints without metadata leave it uninitialized and pay no royalties.
+    #[account(
+        seeds = [b"metadata", METADATA_PROGRAM_ID.as_ref(), auction.token_mint.as_ref()],
+        bump,
+        seeds::program = METADATA_PROGRAM_ID
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// Buyer's loyalty tier, rebated part of the house fee on the purchase
+    #[account(
+        constraint = loyalty.bidder == buyer.key(),
+        constraint = loyalty.auction_house == auction_house.key()
+    )]
+    pub loyalty: Option<Account<'info, LoyaltyAccount>>,
+    /// The seller's index, to free the auction's active slot; auctions created
+    /// before seller indexes existed have none
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), auction.authority.as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    /// Token program of the NFT mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFT's
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSalePrice<'info> {
+    #[account(
+        mut,
+        constraint = auction.auction_house == auction_house.key()
+    )]
+    pub auction: Account<'info, Auction>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromTreasury<'info> {
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of treasury_mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&treasury_mint.key(), registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = Some(fee_withdrawal_destination.key())
+            == auction_house.fee_withdrawal_destination_for(
+                &treasury_mint.key(),
+                registered_mint.as_deref(),
+            )
+    )]
+    pub fee_withdrawal_destination: InterfaceAccount<'info, TokenAccount>,
+    /// The house's treasury mint or a registered one, checked through the treasury
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromFeeAccount<'info> {
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        seeds = [b"auction_house_fee_account", auction_house.key().as_ref()],
+        bump = auction_house.fee_payer_bump,
+        constraint = auction_house_fee_account.key() == auction_house.auction_house_fee_account
+    )]
+    pub auction_house_fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = fee_withdrawal_destination.key() == auction_house.fee_withdrawal_destination
+    )]
+    pub fee_withdrawal_destination: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = treasury_mint.key() == auction_house.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CommitBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = Bid::LEN,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions, which escrow lamports in the auction account
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct RevealBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        constraint = bid.auction == auction.key()
+    )]
+    pub bid: Account<'info, Bid>,
+    pub bidder: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimNft<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = winner_token_account.owner == winner.key(),
+        constraint = winner_token_account.mint == auction.token_mint
+    )]
+    pub winner_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// Winning bidder, or the seller reclaiming an unsold programmable NFT or bundle.
+    /// Pays for any token record a programmable transfer creates.
+    #[account(mut)]
+    pub winner: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(mut)]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    #[account(mut)]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut)]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAuctionHouse<'info> {
+    #[account(mut)]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetAuctionInfo<'info> {
+    pub auction: Account<'info, Auction>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateBid<'info> {
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// CHECK: Wallet the bid would be placed from; only its address is used
+    pub bidder: UncheckedAccount<'info>,
+    /// The bidder's standing bid, if they have bid before
+    #[account(
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Option<Account<'info, Bid>>,
+}
+
+#[derive(Accounts)]
+pub struct BatchEndAuctions<'info> {
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of the batch's treasury mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&treasury_mint.key(), registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = Some(protocol_fee_account.key())
+            == auction_house.protocol_fee_destination_for(
+                &treasury_mint.key(),
+                registered_mint.as_deref(),
+            )
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Treasury mint of every auction in the batch, checked through the treasury
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub settler: Signer<'info>,
+    /// Token program of every NFT mint in the batch
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFTs'
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump,
+        constraint = bid.auction == auction.key()
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(listing_bump: u8)]
+pub struct CreateListing<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Listing::LEN,
+        seeds = [b"listing", token_mint.key().as_ref(), authority.key().as_ref()],
+        bump = listing_bump
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        constraint = auction_house.treasury_mint == treasury_mint.key()
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_account.owner == authority.key(),
+        constraint = token_account.mint == token_mint.key()
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = authority,
+        token::mint = token_mint,
+        token::authority = listing,
+        token::token_program = token_program,
+        seeds = [b"listing_nft", listing.key().as_ref()],
+        bump
+    )]
+    pub listing_nft_account: InterfaceAccount<'info, TokenAccount>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    /// Only required when the auction house has requires_sign_off set
+    pub auction_house_authority: Option<Signer<'info>>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSale<'info> {
+    #[account(
+        mut,
+        close = seller
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        constraint = auction_house.key() == listing.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        constraint = auction_house_treasury.key() == auction_house.auction_house_treasury
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = protocol_fee_account.key() == auction_house.protocol_fee_destination
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = listing_nft_account.key() == listing.token_account
+    )]
+    pub listing_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = buyer_nft_account.owner == buyer.key(),
+        constraint = buyer_nft_account.mint == listing.token_mint
+    )]
+    pub buyer_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Not needed for native SOL listings
+    #[account(
+        mut,
+        constraint = buyer_token_account.owner == buyer.key(),
+        constraint = buyer_token_account.mint == listing.treasury_mint
+    )]
+    pub buyer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL listings
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == listing.authority,
+        constraint = seller_proceeds_account.mint == listing.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Seller wallet, which receives the listing and escrow rent, and the
+    /// proceeds of native SOL listings
+    #[account(
+        mut,
+        constraint = seller.key() == listing.authority
+    )]
+    pub seller: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_mint.key() == listing.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = treasury_mint.key() == listing.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    /// Token program of the NFT mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFT's
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelListing<'info> {
+    #[account(
+        mut,
+        close = authority
+    )]
+    pub listing: Account<'info, Listing>,
+    #[account(
+        mut,
+        constraint = listing_nft_account.key() == listing.token_account
+    )]
+    pub listing_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == authority.key(),
+        constraint = owner_token_account.mint == listing.token_mint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_mint.key() == listing.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(offer_bump: u8)]
+pub struct MakeOffer<'info> {
+    #[account(
+        init,
+        payer = offerer,
+        space = Offer::LEN,
+        seeds = [b"offer", token_mint.key().as_ref(), offerer.key().as_ref()],
+        bump = offer_bump
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(
+        constraint = auction_house.treasury_mint == treasury_mint.key()
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Wallet the offer is made to. It is only recorded here; accept_offer
+    /// requires its signature.
+    pub owner: UncheckedAccount<'info>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = offerer_token_account.owner == offerer.key(),
+        constraint = offerer_token_account.mint == treasury_mint.key()
+    )]
+    pub offerer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Offer-owned treasury escrow, not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = offer_escrow.owner == offer.key(),
+        constraint = offer_escrow.mint == treasury_mint.key()
+    )]
+    pub offer_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub offerer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOffer<'info> {
+    #[account(
+        mut,
+        close = offerer
+    )]
+    pub offer: Account<'info, Offer>,
+    #[account(
+        constraint = auction_house.key() == offer.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        constraint = auction_house_treasury.key() == auction_house.auction_house_treasury
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = protocol_fee_account.key() == auction_house.protocol_fee_destination
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = offer_escrow.owner == offer.key(),
+        constraint = offer_escrow.mint == offer.treasury_mint
+    )]
+    pub offer_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = owner_nft_account.owner == owner.key(),
+        constraint = owner_nft_account.mint == offer.token_mint
+    )]
+    pub owner_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = offerer_nft_account.owner == offer.offerer,
+        constraint = offerer_nft_account.mint == offer.token_mint
+    )]
+    pub offerer_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == owner.key(),
+        constraint = seller_proceeds_account.mint == offer.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Offerer wallet, which receives the offer account rent
+    #[account(
+        mut,
+        constraint = offerer.key() == offer.offerer
+    )]
+    pub offerer: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_mint.key() == offer.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = treasury_mint.key() == offer.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    /// Token program of the NFT mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFT's
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CancelOffer<'info> {
+    #[account(
+        mut,
+        close = offerer
+    )]
+    pub offer: Account<'info, Offer>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = offer_escrow.owner == offer.key(),
+        constraint = offer_escrow.mint == offer.treasury_mint
+    )]
+    pub offer_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = offerer_token_account.owner == offerer.key(),
+        constraint = offerer_token_account.mint == offer.treasury_mint
+    )]
+    pub offerer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == offer.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub offerer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct PlacePennyBid<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of the auction's treasury mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&auction.treasury_mint, registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = Bid::LEN,
+        seeds = [b"bid", auction.key().as_ref(), bidder.key().as_ref()],
+        bump
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions, which escrow lamports in the auction account
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Bid record of the current highest bidder, required once the auction has a bid
+    #[account(mut)]
+    pub previous_bid: Option<Account<'info, Bid>>,
+    #[account(
+        mut,
+        constraint = previous_bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub previous_bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Wallet of the current highest bidder, refunded in native SOL auctions.
+    /// Checked against auction.highest_bidder before any lamports move.
+    #[account(mut)]
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: The house's notify_program, told when this bid outbids someone. If it
+    /// errors the whole bid fails, so it stays optional: leave it out to bid anyway.
+    #[account(
+        constraint = Some(notify_program.key()) == auction_house.notify_program
+    )]
+    pub notify_program: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(escrow_bump: u8)]
+pub struct DepositBidderEscrow<'info> {
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = BidderEscrow::LEN,
+        seeds = [b"bidder_escrow", auction_house.key().as_ref(), bidder.key().as_ref()],
+        bump = escrow_bump
+    )]
+    pub bidder_escrow: Account<'info, BidderEscrow>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == auction_house.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Escrow-owned treasury account, not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_escrow_token_account.owner == bidder_escrow.key(),
+        constraint = bidder_escrow_token_account.mint == auction_house.treasury_mint
+    )]
+    pub bidder_escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == auction_house.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawBidderEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [b"bidder_escrow", bidder_escrow.auction_house.as_ref(), bidder.key().as_ref()],
+        bump = bidder_escrow.bump
+    )]
+    pub bidder_escrow: Account<'info, BidderEscrow>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_token_account.owner == bidder.key(),
+        constraint = bidder_token_account.mint == bidder_escrow.treasury_mint
+    )]
+    pub bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL houses
+    #[account(
+        mut,
+        constraint = bidder_escrow_token_account.owner == bidder_escrow.key(),
+        constraint = bidder_escrow_token_account.mint == bidder_escrow.treasury_mint
+    )]
+    pub bidder_escrow_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = treasury_mint.key() == bidder_escrow.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(loyalty_bump: u8)]
+pub struct SetLoyaltyTier<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = LoyaltyAccount::LEN,
+        seeds = [b"loyalty", auction_house.key().as_ref(), bidder.key().as_ref()],
+        bump = loyalty_bump
+    )]
+    pub loyalty: Account<'info, LoyaltyAccount>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// CHECK: Wallet being assigned the tier; only its key is recorded
+    pub bidder: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendAuction<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// NFT token account of the winner of a settled sale, or of the seller otherwise
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == auction.token_mint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Owner of recipient_token_account, checked in the handler
+    pub recipient: UncheckedAccount<'info>,
+    /// Not needed for native SOL auctions
+    #[account(
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// Auction house authority; pays for any token record a programmable transfer creates
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeAuction<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveDisputedAuction<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Registration of the auction's treasury mint, needed when it is not the house's own
+    #[account(
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Option<Account<'info, RegisteredTreasuryMint>>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == auction.authority,
+        constraint = owner_token_account.mint == auction.token_mint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = Some(auction_house_treasury.key())
+            == auction_house.treasury_for(&auction.treasury_mint, registered_mint.as_deref())
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    /// Receives the protocol fee; only needed when the house charges one
+    #[account(
+        mut,
+        constraint = Some(protocol_fee_account.key())
+            == auction_house.protocol_fee_destination_for(
+                &auction.treasury_mint,
+                registered_mint.as_deref(),
+            )
+    )]
+    pub protocol_fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == auction.proceeds_destination,
+        constraint = seller_proceeds_account.mint == auction.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Proceeds destination wallet, only needed to receive proceeds in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = seller.key() == auction.proceeds_destination
+    )]
+    pub seller: Option<UncheckedAccount<'info>>,
+    /// Referrer's treasury mint token account, needed when the winning bid was referred
+    #[account(
+        mut,
+        constraint = referrer_token_account.mint == auction.treasury_mint
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Referrer wallet, only needed to receive the referral fee in native SOL
+    /// auctions. Checked against auction.referrer before any lamports move.
+    #[account(mut)]
+    pub referrer: Option<UncheckedAccount<'info>>,
+    /// Winner's treasury mint token account, needed when the winner has a loyalty rebate.
+    /// A bidless auction has no winner, so passing one fails instead of panicking.
+    #[account(
+        mut,
+        constraint = Some(winner_token_account.owner) == auction.highest_bidder,
+        constraint = winner_token_account.mint == auction.treasury_mint
+    )]
+    pub winner_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Winner wallet, only needed to receive a loyalty rebate in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = Some(winner.key()) == auction.highest_bidder
+    )]
+    pub winner: Option<UncheckedAccount<'info>>,
+    /// Winning bid record, needed to refund it under RefundAndReturn
+    #[account(mut)]
+    pub winner_bid: Option<Account<'info, Bid>>,
+    /// CHECK: Metaplex metadata PDA of the NFT mint, parsed for creator royalties.
+    /// Mints without metadata leave it uninitialized and pay no royalties.
+    #[account(
+        seeds = [b"metadata", METADATA_PROGRAM_ID.as_ref(), auction.token_mint.as_ref()],
+        bump,
+        seeds::program = METADATA_PROGRAM_ID
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// The seller's index, to free the auction's active slot; auctions created
+    /// before seller indexes existed have none
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), auction.authority.as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    pub authority: Signer<'info>,
+    /// Token program of the NFT mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the treasury mint, which may differ from the NFT's
+    pub treasury_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Relist<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    #[account(
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Bid record of an unsettled high bid that missed the reserve
+    #[account(mut)]
+    pub previous_bid: Option<Account<'info, Bid>>,
+    #[account(
+        mut,
+        constraint = previous_bidder_token_account.mint == auction.treasury_mint
+    )]
+    pub previous_bidder_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Wallet of that high bidder, refunded in native SOL auctions. Checked
+    /// against auction.highest_bidder before any lamports move.
+    #[account(mut)]
+    pub previous_bidder: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    /// The seller's index, needed when the auction released its active slot
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(registered_bump: u8, treasury_bump: u8)]
+pub struct AddTreasuryMint<'info> {
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = RegisteredTreasuryMint::LEN,
+        seeds = [b"treasury_mint", auction_house.key().as_ref(), treasury_mint.key().as_ref()],
+        bump = registered_bump
+    )]
+    pub registered_mint: Account<'info, RegisteredTreasuryMint>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        token::mint = treasury_mint,
+        token::authority = auction_house,
+        token::token_program = token_program,
+        seeds = [
+            b"auction_house_treasury",
+            auction_house.key().as_ref(),
+            treasury_mint.key().as_ref()
+        ],
+        bump = treasury_bump
+    )]
+    pub auction_house_treasury: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveTreasuryMint<'info> {
+    #[account(
+        mut,
+        constraint = registered_mint.auction_house == auction_house.key()
+    )]
+    pub registered_mint: Account<'info, RegisteredTreasuryMint>,
+    pub auction_house: Account<'info, AuctionHouse>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VerifySettlement<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    /// Not needed for native SOL auctions
+    #[account(
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct AbortUnbidAuction<'info> {
+    #[account(
+        mut,
+        close = authority
+    )]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        constraint = auction_nft_account.key() == auction.token_account
+    )]
+    pub auction_nft_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == auction.authority,
+        constraint = owner_token_account.mint == auction.token_mint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        constraint = token_mint.key() == auction.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// Seller's auction lookup, which drops the closed auction. Auctions listed
+    /// before seller indexes existed have none.
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction.auction_house.as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    /// Seller; receives the rent and pays for any token record a programmable
+    /// transfer creates
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimBond<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// Bid record of the winner
+    #[account(
+        mut,
+        constraint = bid.auction == auction.key()
+    )]
+    pub bid: Account<'info, Bid>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = auction_token_account.owner == auction.key(),
+        constraint = auction_token_account.mint == auction.treasury_mint
+    )]
+    pub auction_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// Not needed for native SOL auctions
+    #[account(
+        mut,
+        constraint = seller_proceeds_account.owner == auction.proceeds_destination,
+        constraint = seller_proceeds_account.mint == auction.treasury_mint
+    )]
+    pub seller_proceeds_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: Proceeds destination wallet, only needed to receive the bond in native SOL
+    /// auctions
+    #[account(
+        mut,
+        constraint = seller.key() == auction.proceeds_destination
+    )]
+    pub seller: Option<UncheckedAccount<'info>>,
+    #[account(
+        constraint = treasury_mint.key() == auction.treasury_mint
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub caller: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuctionAuthority<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        constraint = auction_house.key() == auction.auction_house
+    )]
+    pub auction_house: Account<'info, AuctionHouse>,
+    /// The current seller's index, needed when the auction holds an active slot
+    #[account(
+        mut,
+        seeds = [b"seller_index", auction_house.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub seller_index: Option<Account<'info, SellerIndex>>,
+    /// Lookup of the new seller's auctions, created if this is their first
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = SellerIndex::LEN,
+        seeds = [
+            b"seller_index",
+            auction_house.key().as_ref(),
+            new_authority.key().as_ref()
+        ],
+        bump
+    )]
+    pub new_seller_index: Account<'info, SellerIndex>,
+    /// CHECK: Wallet taking over the auction; only its address is used
+    #[account(
+        constraint = new_authority.key() != authority.key()
+    )]
+    pub new_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepClosed<'info> {
+    /// Bidder or seller who paid the rent of every account swept
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAuction<'info> {
+    /// CHECK: Possibly written under an older Auction layout, so it is checked and
+    /// deserialized by hand after being reallocated
+    #[account(mut)]
+    pub auction: UncheckedAccount<'info>,
+    /// House of the auction, needed when its authority rather than the seller
+    /// migrates it, and for auctions from before Auction recorded its house
+    pub auction_house: Option<Account<'info, AuctionHouse>>,
+    /// Seller or house authority; pays any rent the larger layout needs
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateAuctionHouse<'info> {
+    /// CHECK: Possibly written under the original AuctionHouse layout, so it is
+    /// checked and decoded by hand
+    #[account(mut)]
+    pub auction_house: UncheckedAccount<'info>,
+    /// House authority; pays the rent the larger layout needs
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReleaseSellerSlot<'info> {
+    #[account(mut)]
+    pub auction: Account<'info, Auction>,
+    #[account(
+        mut,
+        seeds = [
+            b"seller_index",
+            auction.auction_house.as_ref(),
+            auction.authority.as_ref()
+        ],
+        bump
+    )]
+    pub seller_index: Account<'info, SellerIndex>,
+}
+
+#[derive(Accounts)]
+pub struct GetSellerIndex<'info> {
+    pub seller_index: Account<'info, SellerIndex>,
+}
+
+#[account]
+pub struct AuctionHouse {
+    pub authority: Pubkey,
+    pub treasury_mint: Pubkey,
+    pub auction_house_fee_account: Pubkey,
+    pub auction_house_treasury: Pubkey,
+    pub fee_withdrawal_destination: Pubkey,
+    pub fee_payer_bump: u8,
+    pub treasury_bump: u8,
+    pub seller_fee_basis_points: u16,
+    pub requires_sign_off: bool,
+    pub can_change_sale_price: bool,
+    pub allow_self_bid: bool,
+    /// Blocks new listings, bids and purchases while set
+    pub paused: bool,
+    /// Authority that initialized the house. The PDA is derived from it, so it
+    /// stays fixed when the authority is transferred.
+    pub creator: Pubkey,
+    /// Proposed new authority, which must accept before the transfer takes effect
+    pub pending_authority: Option<Pubkey>,
+    /// Shortest time in seconds an auction may stay open for bidding
+    pub min_auction_duration: i64,
+    /// Longest time in seconds from creation to end_time; zero disables the limit
+    pub max_auction_duration: i64,
+    /// Seconds after bidding closes before an auction can be settled. Both the payout
+    /// and NFT delivery wait for it, so a disputed sale can still be unwound.
+    pub settlement_delay: i64,
+    /// Lets the highest bidder retract through cancel_bid until anyone else bids
+    pub allow_bid_retraction: bool,
+    /// Platform cut taken at settlement on top of seller_fee_basis_points
+    pub protocol_fee_basis_points: u16,
+    /// Treasury mint token account receiving the protocol fee
+    pub protocol_fee_destination: Pubkey,
+    /// Part of the house fee paid to the referrer of a winning bid, as basis points
+    /// of the sale price; at most seller_fee_basis_points
+    pub referral_fee_basis_points: u16,
+    /// Verified Metaplex collection every listed NFT must belong to; None allows any
+    pub allowed_collection: Option<Pubkey>,
+    /// Who wins ties for the units left over when a sale is split into fees,
+    /// royalties and proceeds
+    pub rounding_policy: RoundingPolicy,
+    /// Treasury balance above which end_auction forwards the excess to
+    /// fee_withdrawal_destination; zero disables the sweep
+    pub treasury_sweep_threshold: u64,
+    /// Lets sellers cancel auctions that already have a bid; bidders then withdraw
+    /// their escrow in full
+    pub allow_cancel_after_bid: bool,
+    /// Most auctions a seller may have open at once, counted by their SellerIndex;
+    /// zero is unlimited
+    pub max_active_auctions_per_seller: u32,
+    /// Seconds after created_at in which sellers may cancel an auction even with
+    /// bids, whatever allow_cancel_after_bid says; zero disables the grace period
+    pub creation_cancel_window: i64,
+    /// Whether house and protocol fees come out of the seller's proceeds or are paid
+    /// by the winner on top of the price; auctions keep the model they were listed
+    /// under
+    pub fee_payer_model: FeePayerModel,
+    /// Largest bid, proxy ceiling or sealed collateral accepted, a guard against
+    /// mistyped amounts; u64::MAX disables it
+    pub absolute_max_bid: u64,
+    /// Program sent an OUTBID_NOTIFICATION_TAG message when a bid outbids someone.
+    /// A failed CPI cannot be caught, so a broken or hostile program fails every bid
+    /// that passes it; bidders opt in per transaction and can always leave it out.
+    pub notify_program: Option<Pubkey>,
+    pub bump: u8,
+}
+
+#[account]
+pub struct Auction {
+    pub authority: Pubkey,
+    pub auction_house: Pubkey,
+    pub token_mint: Pubkey,
+    /// Auction-owned escrow holding the NFT while it is listed
+    pub token_account: Pubkey,
+    pub treasury_mint: Pubkey,
+    /// Decimals of treasury_mint, read at creation, so clients can scale the raw
+    /// base-unit prices and bids for display
+    pub treasury_decimals: u8,
+    pub auction_type: u8,
+    /// English auctions only: whether the winner pays their standing bid or one
+    /// increment over the runner-up
+    pub pricing_rule: PricingRule,
+    pub token_size: u64,
+    /// Bids, buy now and reserve prices are per unit, and the winner pays for all
+    /// token_size copies
+    pub multi_unit: bool,
+    pub minimum_price: u64,
+    /// Smallest opening bid, higher than minimum_price; zero leaves the first bid
+    /// only having to exceed minimum_price
+    pub opening_bid: u64,
+    /// Smallest bid in base units, typically one display unit of the treasury mint so
+    /// dust bids are rejected; zero disables the floor
+    pub min_bid_raw: u64,
+    /// Price at which the auction can be bought outright; zero disables buy now
+    pub buy_now_price: u64,
+    /// Lowest winning bid the seller will accept; below it the NFT returns to the seller
+    pub reserve_price: u64,
+    /// Dutch auction price at start_time
+    pub start_price: u64,
+    /// Dutch auction price from end_time onwards
+    pub end_price: u64,
+    pub current_price: u64,
+    /// Smallest raise over current_price once the auction has a bid. The opening
+    /// bid only has to exceed minimum_price, since current_price starts there.
+    pub min_bid_increment: u64,
+    /// Raise as basis points of current_price; the larger of the two increments applies
+    pub min_bid_increment_bps: u16,
+    /// English auctions only: increments that step up with the price, ascending by
+    /// threshold. The tier containing current_price applies when it is the largest
+    /// increment.
+    pub increment_tiers: Vec<IncrementTier>,
+    /// Penny auctions only: non-refundable fee paid to the house treasury with every bid
+    pub bid_fee: u64,
+    /// English auctions only: escrowed once per bidder on top of their bid. Losers
+    /// get it back with their bid and the winner once they claim, unless they miss
+    /// the claim window and reclaim_bond forfeits it to the seller.
+    pub bid_bond: u64,
+    /// Seconds after the settlement window in which the winner must claim the NFT
+    pub bond_claim_window: i64,
+    /// House fee charged on this auction's sale instead of seller_fee_basis_points,
+    /// set by the house authority for promotions
+    pub fee_override: Option<u16>,
+    /// When create_auction or relist ran; the house's max_auction_duration and
+    /// creation_cancel_window are measured from it
+    pub created_at: i64,
+    /// Bidding opens at start_time; the auction is Pending until then
+    pub start_time: i64,
+    pub end_time: i64,
+    /// Seconds before end_time in which a bid extends the auction; zero disables extension
+    pub extension_window: i64,
+    /// Seconds added to end_time by each bid inside the extension window
+    pub extension_amount: i64,
+    /// Latest time bid extensions may push end_time to; later bids still count but
+    /// no longer extend. Zero leaves extensions unbounded.
+    pub max_end_time: i64,
+    /// Minimum seconds between consecutive bids; zero disables the throttle
+    pub min_bid_interval: i64,
+    /// Sealed auctions only: bids may be revealed from end_time until this time
+    pub reveal_end_time: i64,
+    /// Sealed auctions: second-highest revealed bid, while current_price holds the
+    /// highest. English auctions: highest bid of anyone other than the leader.
+    pub second_highest_bid: u64,
+    pub highest_bidder: Option<Pubkey>,
+    /// Front-end credited with the highest bid, paid part of the house fee on a sale
+    pub referrer: Option<Pubkey>,
+    /// Loyalty rebate of the highest bidder, recorded when they took the lead or bought
+    /// the auction outright
+    pub winner_rebate_basis_points: u16,
+    /// Set exactly once, when the sale or its failure is settled
+    pub settled: bool,
+    /// Signer that called end_auction
+    pub settled_by: Option<Pubkey>,
+    /// Set once the winner has received the NFT
+    pub claimed: bool,
+    /// Treasury funds paid into escrow over the auction's lifetime
+    pub escrowed_total: u64,
+    /// Treasury funds paid out of escrow: refunds, fees, royalties and proceeds
+    pub withdrawn_total: u64,
+    /// Set by verify_settlement once every escrowed payment has been paid out
+    pub settlement_verified: bool,
+    /// Metaplex programmable NFT, moved through the metadata program's transfer
+    pub programmable: bool,
+    /// Further mints sold with token_mint as one lot, one each, held in
+    /// auction-owned token accounts. Empty outside bundle auctions.
+    pub bundle: Vec<Pubkey>,
+    /// Number of accepted bids, including raises of an existing bid
+    pub bid_count: u64,
+    pub last_bid_time: i64,
+    /// Merkle root of the wallets allowed to bid; all zeros leaves bidding open
+    pub allowlist_root: [u8; 32],
+    /// Zero-padded UTF-8 display name
+    pub name: [u8; MAX_AUCTION_NAME_LEN],
+    /// Zero-padded UTF-8 link to off-chain auction details
+    pub uri: [u8; MAX_AUCTION_URI_LEN],
+    /// Borsh encodes the enum as a single byte, the same layout as the former u8
+    pub status: AuctionStatus,
+    /// Set by the house authority to halt bids, purchases, settlement and NFT
+    /// delivery pending moderation or a dispute; withdrawals and cancellation are
+    /// unaffected
+    pub frozen: bool,
+    /// How resolve_disputed_auction settled a dispute over this auction, if any
+    pub resolution: Option<Resolution>,
+    /// Whether the auction counts towards its seller's active auction limit
+    pub holds_seller_slot: bool,
+    pub bump: u8,
+    /// AUCTION_VERSION the account was last written under; fields appended since
+    /// read as zero until migrate_auction brings it up to date
+    pub version: u8,
+    /// Last BID_HISTORY_LEN bids, oldest overwritten first, for display only: Bid
+    /// accounts and escrow totals remain the record of what is owed. Unused slots
+    /// have a default bidder.
+    pub bid_history: [BidRecord; BID_HISTORY_LEN],
+    /// Slot in bid_history the next bid is written to, and the oldest once full
+    pub bid_history_head: u8,
+    /// Wallet paid the seller's proceeds and forfeited bonds, which may differ from
+    /// the authority that listed the auction
+    pub proceeds_destination: Pubkey,
+    /// House and protocol fees the winner pays on top of the price, fixed at listing
+    /// from a BuyerPays house; zero when the seller pays them
+    pub buyer_premium_basis_points: u16,
+    /// Seller the auction PDA was derived from and signs with; authority differs once
+    /// transfer_auction_authority has handed the auction over
+    pub original_authority: Pubkey,
+}
+
+#[account]
+pub struct Bid {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    /// Escrowed bid; English bids are zeroed when outbid and refunded
+    pub amount: u64,
+    /// Proxy ceiling up to which the program raises the bid automatically. It is
+    /// escrowed in full while the bidder leads; the unused part is withdrawable after
+    /// settlement.
+    pub max_bid: u64,
+    /// Bid bond escrowed alongside the bid, zero once forfeited
+    pub bond: u64,
+    pub timestamp: i64,
+    /// Auction bid_count after this bidder's latest bid, ordering bids that land in
+    /// the same slot
+    pub bid_nonce: u64,
+    /// Sealed auctions only: hash of the bid amount and nonce submitted by commit_bid
+    pub commitment: [u8; 32],
+    pub revealed: bool,
+}
+
+/// Treasury balance a bidder funds once per auction house and bids from many times
+#[account]
+pub struct BidderEscrow {
+    pub auction_house: Pubkey,
+    pub bidder: Pubkey,
+    pub treasury_mint: Pubkey,
+    /// Funds held in the escrow, free to bid with or withdraw
+    pub available: u64,
+    /// Total moved from this escrow into auction bid escrows. Refunds of those bids
+    /// return to the bidder's wallet like any other bid refund.
+    pub committed: u64,
+    /// Nonce the bidder's next signed bid must carry; each relayed bid consumes one
+    pub bid_signature_nonce: u64,
+    pub bump: u8,
+}
+
+/// Treasury mint other than the house's own that auctions may be priced in, with the
+/// accounts its fees are paid to. Listings, offers and bidder escrows stay in the
+/// house's treasury_mint.
+#[account]
+pub struct RegisteredTreasuryMint {
+    pub auction_house: Pubkey,
+    pub treasury_mint: Pubkey,
+    /// House-owned token account collecting fees in this mint
+    pub treasury: Pubkey,
+    pub fee_withdrawal_destination: Pubkey,
+    pub protocol_fee_destination: Pubkey,
+    /// Cleared by remove_treasury_mint, which stops new auctions in the mint while
+    /// existing ones settle as usual
+    pub enabled: bool,
+    pub treasury_bump: u8,
+    pub bump: u8,
+}
+
+/// A seller's most recent auctions, so clients can list them without scanning
+/// program accounts
+#[account]
+pub struct SellerIndex {
+    pub authority: Pubkey,
+    /// Auctions ever recorded, including any that have dropped off the list
+    pub total_auctions: u64,
+    /// Auctions holding one of the seller's active slots: open ones, and final ones
+    /// whose slot neither settlement nor release_seller_slot has freed
+    pub active_auctions: u32,
+    /// Oldest first, at most MAX_SELLER_INDEX_AUCTIONS
+    pub auctions: Vec<Pubkey>,
+}
+
+/// Fee rebate tier the house authority assigns to a bidder
+#[account]
+pub struct LoyaltyAccount {
+    pub auction_house: Pubkey,
+    pub bidder: Pubkey,
+    /// Share of the house's fee refunded to this bidder when they win an auction
+    pub rebate_basis_points: u16,
+    pub bump: u8,
+}
+
+/// Fixed-price sale of an escrowed NFT, bought outright by the first buyer to pay
+/// the price
+#[account]
+pub struct Listing {
+    pub authority: Pubkey,
+    pub auction_house: Pubkey,
+    pub token_mint: Pubkey,
+    /// Listing-owned escrow holding the NFT until it sells or is cancelled
+    pub token_account: Pubkey,
+    pub treasury_mint: Pubkey,
+    pub token_size: u64,
+    pub price: u64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+/// Standing offer on an NFT that is not listed, escrowing the offered amount until
+/// the owner accepts or the offerer cancels
+#[account]
+pub struct Offer {
+    pub offerer: Pubkey,
+    pub auction_house: Pubkey,
+    pub token_mint: Pubkey,
+    pub treasury_mint: Pubkey,
+    /// Wallet holding the NFT, the only one that can accept
+    pub owner: Pubkey,
+    pub token_size: u64,
+    /// Amount escrowed and paid to the owner, less fees, on acceptance
+    pub amount: u64,
+    /// The offer can be accepted until this time; the offerer can cancel at any time
+    pub offer_expiry: i64,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+/// House configuration for initialize_auction_house
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InitializeAuctionHouseArgs {
+    pub seller_fee_basis_points: u16,
+    pub requires_sign_off: bool,
+    pub can_change_sale_price: bool,
+    /// Lets auction authorities bid on their own listings
+    pub allow_self_bid: bool,
+    pub min_auction_duration: i64,
+    /// Zero leaves auction length unbounded
+    pub max_auction_duration: i64,
+    pub settlement_delay: i64,
+    /// Lets a sole bidder retract their bid through cancel_bid
+    pub allow_bid_retraction: bool,
+    /// Together with seller_fee_basis_points, at most 10000
+    pub protocol_fee_basis_points: u16,
+    pub protocol_fee_destination: Pubkey,
+    /// Share of the house fee paid to referrers, at most seller_fee_basis_points
+    pub referral_fee_basis_points: u16,
+    pub allowed_collection: Option<Pubkey>,
+    /// Zero disables automatic sweeps of the treasury
+    pub treasury_sweep_threshold: u64,
+    pub rounding_policy: RoundingPolicy,
+    /// Lets sellers cancel auctions that already have a bid
+    pub allow_cancel_after_bid: bool,
+    /// Zero leaves the number of open auctions per seller unlimited
+    pub max_active_auctions_per_seller: u32,
+    /// Grace period after creation for cancelling auctions; zero disables it
+    pub creation_cancel_window: i64,
+    pub fee_payer_model: FeePayerModel,
+    /// None disables the bid ceiling
+    pub absolute_max_bid: Option<u64>,
+    pub notify_program: Option<Pubkey>,
+}
+
+/// Settings changed by update_auction_house; None leaves a setting as it is. The
+/// treasury mint and PDA-derived accounts are fixed at initialization.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UpdateAuctionHouseArgs {
+    pub seller_fee_basis_points: Option<u16>,
+    pub requires_sign_off: Option<bool>,
+    pub can_change_sale_price: Option<bool>,
+    pub allow_self_bid: Option<bool>,
+    pub fee_withdrawal_destination: Option<Pubkey>,
+    pub min_auction_duration: Option<i64>,
+    pub max_auction_duration: Option<i64>,
+    pub settlement_delay: Option<i64>,
+    pub allow_bid_retraction: Option<bool>,
+    pub protocol_fee_basis_points: Option<u16>,
+    pub protocol_fee_destination: Option<Pubkey>,
+    pub referral_fee_basis_points: Option<u16>,
+    /// Some(None) lifts the collection restriction
+    pub allowed_collection: Option<Option<Pubkey>>,
+    pub treasury_sweep_threshold: Option<u64>,
+    pub rounding_policy: Option<RoundingPolicy>,
+    pub allow_cancel_after_bid: Option<bool>,
+    pub max_active_auctions_per_seller: Option<u32>,
+    pub creation_cancel_window: Option<i64>,
+    pub fee_payer_model: Option<FeePayerModel>,
+    /// Some(u64::MAX) disables the bid ceiling
+    pub absolute_max_bid: Option<u64>,
+    /// Some(None) turns outbid notifications off
+    pub notify_program: Option<Option<Pubkey>>,
+}
+
+/// New terms for relist; None keeps the auction's current price
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RelistArgs {
+    pub end_time: i64,
+    /// Cap on end_time for bid extensions in the new run, at or after end_time. The
+    /// previous run's cap is never kept, so None, like zero, leaves them unbounded.
+    pub max_end_time: Option<i64>,
+    pub minimum_price: Option<u64>,
+    pub reserve_price: Option<u64>,
+}
+
+/// Snapshot returned by get_auction_info
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct AuctionInfo {
+    /// Status as it would be if the auction were settled now
+    pub status: AuctionStatus,
+    /// Seconds until end_time, zero once it has passed
+    pub seconds_remaining: u64,
+    /// Highest bid, or the Dutch price at the current time
+    pub current_price: u64,
+    /// Smallest bid that place_bid would currently accept
+    pub min_next_bid: u64,
+}
+
+/// Auction state after place_bid, returned so clients can show the outcome of a
+/// simulated or confirmed bid without fetching the auction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BidResult {
+    /// Whether the bidder holds the lead after this bid
+    pub is_leading: bool,
+    pub new_current_price: u64,
+    /// Smallest bid that place_bid would accept next
+    pub min_next_bid: u64,
+    /// End time including any extension this bid triggered
+    pub end_time: i64,
+}
+
+/// Outcome of simulate_bid: Accepted, or the check place_bid would fail, named after
+/// the error it would fail with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum BidCheck {
+    Accepted,
+    AuctionHousePaused,
+    AuctionFrozen,
+    AuctionNotStarted,
+    AuctionNotActive,
+    AuctionEnded,
+    BiddingTooFast,
+    WrongAuctionType,
+    SelfBiddingNotAllowed,
+    BidderNotAllowlisted,
+    InvalidMaxBid,
+    BidExceedsCeiling,
+    IncreaseOnly,
+    BidTooLow,
+    BidBelowMinimumUnit,
+    BidIncrementTooSmall,
+    OpeningBidTooLow,
+}
+
+impl BidCheck {
+    /// Fails with the error matching a rejected bid
+    pub fn require_accepted(self) -> Result<()> {
+        let error = match self {
+            BidCheck::Accepted => return Ok(()),
+            BidCheck::AuctionHousePaused => AuctionHouseError::AuctionHousePaused,
+            BidCheck::AuctionFrozen => AuctionHouseError::AuctionFrozen,
+            BidCheck::AuctionNotStarted => AuctionHouseError::AuctionNotStarted,
+            BidCheck::AuctionNotActive => AuctionHouseError::AuctionNotActive,
+            BidCheck::AuctionEnded => AuctionHouseError::AuctionEnded,
+            BidCheck::BiddingTooFast => AuctionHouseError::BiddingTooFast,
+            BidCheck::WrongAuctionType => AuctionHouseError::WrongAuctionType,
+            BidCheck::SelfBiddingNotAllowed => AuctionHouseError::SelfBiddingNotAllowed,
+            BidCheck::BidderNotAllowlisted => AuctionHouseError::BidderNotAllowlisted,
+            BidCheck::InvalidMaxBid => AuctionHouseError::InvalidMaxBid,
+            BidCheck::BidExceedsCeiling => AuctionHouseError::BidExceedsCeiling,
+            BidCheck::IncreaseOnly => AuctionHouseError::IncreaseOnly,
+            BidCheck::BidTooLow => AuctionHouseError::BidTooLow,
+            BidCheck::BidBelowMinimumUnit => AuctionHouseError::BidBelowMinimumUnit,
+            BidCheck::BidIncrementTooSmall => AuctionHouseError::BidIncrementTooSmall,
+            BidCheck::OpeningBidTooLow => AuctionHouseError::OpeningBidTooLow,
+        };
+        Err(error.into())
+    }
+}
+
+/// Listing parameters for create_auction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateAuctionArgs {
+    pub token_size: u64,
+    /// Sell token_size copies with every price quoted per unit
+    pub multi_unit: bool,
+    pub minimum_price: u64,
+    /// Floor for the first bid; zero falls back to minimum_price
+    pub opening_bid: u64,
+    /// Floor for every bid in raw base units; zero disables it
+    pub min_bid_raw: u64,
+    pub end_time: i64,
+    pub min_bid_increment: u64,
+    pub min_bid_increment_bps: u16,
+    /// At most MAX_INCREMENT_TIERS, with strictly ascending thresholds
+    pub increment_tiers: Vec<IncrementTier>,
+    pub extension_window: i64,
+    pub extension_amount: i64,
+    /// Cap on end_time for bid extensions, at or after end_time; zero is unbounded
+    pub max_end_time: i64,
+    /// Seconds that must pass after any bid before the next is accepted; zero disables
+    pub min_bid_interval: i64,
+    /// Penny auctions only: fee charged per bid
+    pub bid_fee: u64,
+    /// English auctions only: bond each bidder posts with their first bid; zero disables
+    pub bid_bond: u64,
+    /// Seconds after the settlement window in which the winner must claim to keep
+    /// their bond
+    pub bond_claim_window: i64,
+    /// Promotional house fee below the house default; needs the house authority's
+    /// signature
+    pub fee_override: Option<u16>,
+    /// Wallet to pay the seller's proceeds to, such as a treasury or multisig; None
+    /// pays the authority
+    pub proceeds_destination: Option<Pubkey>,
+    pub buy_now_price: u64,
+    pub reserve_price: u64,
+    /// AuctionType as u8
+    pub auction_type: u8,
+    /// English auctions only: what the winner pays at settlement
+    pub pricing_rule: PricingRule,
+    /// Dutch auctions only: price at start_time
+    pub start_price: u64,
+    /// Dutch auctions only: floor price reached at end_time
+    pub end_price: u64,
+    pub start_time: i64,
+    /// Sealed auctions only: seconds after end_time during which bids can be revealed
+    pub reveal_window: i64,
+    /// Merkle root of allowlisted bidders, or all zeros for an open auction
+    pub allowlist_root: [u8; 32],
+    /// Display name, at most MAX_AUCTION_NAME_LEN bytes
+    pub name: String,
+    /// Off-chain details link, at most MAX_AUCTION_URI_LEN bytes
+    pub uri: String,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum AuctionType {
+    EnglishAscending,
+    DutchDescending,
+    SealedSecondPrice,
+    /// Every bid pays a non-refundable bid_fee, raises the price by min_bid_increment
+    /// and adds extension_amount to the clock; the last bidder wins
+    PennyAuction,
+}
+
+/// What the winner of an English auction pays at settlement
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PricingRule {
+    /// The winning bid as it stands, current_price
+    FirstPrice,
+    /// One increment over the runner-up's highest bid, never more than the winning
+    /// bid; the winner withdraws the rest of their escrow with withdraw_bid
+    SecondPrice,
+}
+
+/// Who wins ties for the units left over when a sale price does not split evenly
+/// into fees, royalties and proceeds
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RoundingPolicy {
+    /// The seller's proceeds win ties for a leftover unit
+    RemainderToSeller,
+    /// The house fee wins ties for a leftover unit
+    RemainderToTreasury,
+}
+
+/// Who bears the house and protocol fees on a sale
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FeePayerModel {
+    /// Fees are deducted from the seller's proceeds
+    SellerPays,
+    /// The winner escrows the fees on top of their bid and the seller receives the
+    /// full price, less royalties
+    BuyerPays,
+}
+
+/// Entry in an auction's bid_history
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BidRecord {
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Bid increment that applies from `threshold` upwards, until the next tier's
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub struct IncrementTier {
+    pub threshold: u64,
+    pub increment: u64,
+}
+
+/// Outcome the house authority picks for a disputed auction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Settle the sale to the winner as end_auction would
+    CompleteSale,
+    /// Refund the winner and return the NFT to the seller
+    RefundAndReturn,
+}
+
+/// What a FeePaid event was charged for
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FeeKind {
+    /// House fee kept in the treasury
+    House,
+    /// Protocol fee paid to the protocol fee destination
+    Protocol,
+    /// Share of the house fee paid to the referring front-end
+    Referral,
+    /// Share of the house fee returned to a loyalty-tier winner
+    Rebate,
+    /// Penny auction bid fee
+    BidFee,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuctionStatus {
+    Active,
+    Ended,
+    Cancelled,
+    ReserveNotMet,
+    Pending,
+}
+
+/// AuctionHouse as the original initialize_auction_house wrote it, before any of
+/// the settings after can_change_sale_price existed
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AuctionHouseV1 {
+    pub authority: Pubkey,
+    pub treasury_mint: Pubkey,
+    pub auction_house_fee_account: Pubkey,
+    pub auction_house_treasury: Pubkey,
+    pub fee_withdrawal_destination: Pubkey,
+    pub fee_payer_bump: u8,
+    pub treasury_bump: u8,
+    pub seller_fee_basis_points: u16,
+    pub requires_sign_off: bool,
+    pub can_change_sale_price: bool,
+    pub bump: u8,
+}
+
+impl AuctionHouseV1 {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 1 + 1 + 2 + 1 + 1 + 1;
+
+    /// The same house under the current layout. The PDA was derived from the
+    /// original authority, so it is also the creator.
+    pub fn upgrade(self) -> Result<AuctionHouse> {
+        let mut auction_house = AuctionHouse::deserialize(&mut &vec![0u8; AuctionHouse::LEN][..])?;
+        auction_house.authority = self.authority;
+        auction_house.creator = self.authority;
+        auction_house.treasury_mint = self.treasury_mint;
+        auction_house.auction_house_fee_account = self.auction_house_fee_account;
+        auction_house.auction_house_treasury = self.auction_house_treasury;
+        auction_house.fee_withdrawal_destination = self.fee_withdrawal_destination;
+        auction_house.fee_payer_bump = self.fee_payer_bump;
+        auction_house.treasury_bump = self.treasury_bump;
+        auction_house.seller_fee_basis_points = self.seller_fee_basis_points;
+        auction_house.requires_sign_off = self.requires_sign_off;
+        auction_house.can_change_sale_price = self.can_change_sale_price;
+        auction_house.fee_payer_model = FeePayerModel::SellerPays;
+        auction_house.rounding_policy = RoundingPolicy::RemainderToSeller;
+        auction_house.absolute_max_bid = u64::MAX;
+        auction_house.bump = self.bump;
+        Ok(auction_house)
+    }
+}
+
+/// Auction as the original create_auction wrote it, before Auction recorded its
+/// house or had a version. Its status was a raw AuctionStatus index.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AuctionV1 {
+    pub authority: Pubkey,
+    pub token_mint: Pubkey,
+    pub token_account: Pubkey,
     pub treasury_mint: Pubkey,
     pub token_size: u64,
     pub minimum_price: u64,
-    pub current_price: u64,
-    pub end_time: i64,
-    pub highest_bidder: Option<Pubkey>,
-    pub status: u8,
-    pub bump: u8,
+    pub current_price: u64,
+    pub end_time: i64,
+    pub highest_bidder: Option<Pubkey>,
+    pub status: u8,
+    pub bump: u8,
+}
+
+impl AuctionV1 {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 33 + 1 + 1;
+
+    /// The same English auction under the current layout, listed on
+    /// `auction_house`; every field the original layout lacked reads as zero
+    pub fn upgrade(self, auction_house: Pubkey) -> Result<Auction> {
+        let mut auction = Auction::deserialize(&mut &vec![0u8; Auction::LEN][..])?;
+        auction.authority = self.authority;
+        auction.auction_house = auction_house;
+        auction.token_mint = self.token_mint;
+        auction.token_account = self.token_account;
+        auction.treasury_mint = self.treasury_mint;
+        auction.auction_type = AuctionType::EnglishAscending as u8;
+        auction.pricing_rule = PricingRule::FirstPrice;
+        auction.token_size = self.token_size;
+        auction.minimum_price = self.minimum_price;
+        auction.current_price = self.current_price;
+        auction.end_time = self.end_time;
+        auction.highest_bidder = self.highest_bidder;
+        auction.status = match self.status {
+            0 => AuctionStatus::Active,
+            1 => AuctionStatus::Ended,
+            2 => AuctionStatus::Cancelled,
+            _ => return err!(AuctionHouseError::InvalidAuctionAccount),
+        };
+        auction.bump = self.bump;
+        Ok(auction)
+    }
+}
+
+impl AuctionHouse {
+    pub const LEN: usize = 8
+        + 32
+        + 32
+        + 32
+        + 32
+        + 32
+        + 1
+        + 1
+        + 2
+        + 1
+        + 1
+        + 1
+        + 1
+        + 1
+        + 32
+        + 33
+        + 8
+        + 8
+        + 8
+        + 1
+        + 2
+        + 32
+        + 2
+        + 33
+        + 1
+        + 8
+        + 1
+        + 4
+        + 8
+        + 1
+        + 8
+        + 33;
+
+    /// Whether new auctions may be priced in `mint`: the house's own treasury mint, or
+    /// one registered with add_treasury_mint and not since removed
+    pub fn accepts_treasury_mint(
+        &self,
+        mint: &Pubkey,
+        registered: Option<&RegisteredTreasuryMint>,
+    ) -> bool {
+        *mint == self.treasury_mint
+            || registered
+                .is_some_and(|registered| registered.treasury_mint == *mint && registered.enabled)
+    }
+
+    /// Treasury collecting fees paid in `mint`. Removed mints keep their treasury so
+    /// auctions already priced in them still settle.
+    pub fn treasury_for(
+        &self,
+        mint: &Pubkey,
+        registered: Option<&RegisteredTreasuryMint>,
+    ) -> Option<Pubkey> {
+        if *mint == self.treasury_mint {
+            return Some(self.auction_house_treasury);
+        }
+
+        registered
+            .filter(|registered| registered.treasury_mint == *mint)
+            .map(|registered| registered.treasury)
+    }
+
+    /// Account receiving the protocol fee on sales paid in `mint`
+    pub fn protocol_fee_destination_for(
+        &self,
+        mint: &Pubkey,
+        registered: Option<&RegisteredTreasuryMint>,
+    ) -> Option<Pubkey> {
+        if *mint == self.treasury_mint {
+            return Some(self.protocol_fee_destination);
+        }
+
+        registered
+            .filter(|registered| registered.treasury_mint == *mint)
+            .map(|registered| registered.protocol_fee_destination)
+    }
+
+    /// Account treasury withdrawals and sweeps in `mint` are paid to
+    pub fn fee_withdrawal_destination_for(
+        &self,
+        mint: &Pubkey,
+        registered: Option<&RegisteredTreasuryMint>,
+    ) -> Option<Pubkey> {
+        if *mint == self.treasury_mint {
+            return Some(self.fee_withdrawal_destination);
+        }
+
+        registered
+            .filter(|registered| registered.treasury_mint == *mint)
+            .map(|registered| registered.fee_withdrawal_destination)
+    }
+
+    /// Splits a sale price into (house fee, protocol fee, creator royalties, seller
+    /// proceeds) in a single allocate_largest_remainder pass over their basis points,
+    /// charging the house fee at `fee_basis_points` and royalties at
+    /// `royalty_basis_points`, capped at what the fees leave of the price. The parts
+    /// always add up to `price` exactly; rounding_policy decides whether the seller
+    /// or the house wins ties for a leftover unit.
+    pub fn split_sale(
+        &self,
+        price: u64,
+        fee_basis_points: u16,
+        royalty_basis_points: u16,
+    ) -> Result<(u64, u64, u64, u64)> {
+        let unreserved_basis_points = 10_000u16
+            .checked_sub(fee_basis_points)
+            .and_then(|bps| bps.checked_sub(self.protocol_fee_basis_points))
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        // Metadata may ask for up to the whole price, which would otherwise leave
+        // the sale unsettleable with the winner's escrow locked
+        let royalty_basis_points = royalty_basis_points.min(unreserved_basis_points);
+        let seller_basis_points = unreserved_basis_points - royalty_basis_points;
+        let favoured = match self.rounding_policy {
+            RoundingPolicy::RemainderToSeller => 3,
+            RoundingPolicy::RemainderToTreasury => 0,
+        };
+        let parts = allocate_largest_remainder(
+            price,
+            &[
+                fee_basis_points as u64,
+                self.protocol_fee_basis_points as u64,
+                royalty_basis_points as u64,
+                seller_basis_points as u64,
+            ],
+            favoured,
+        )?;
+
+        Ok((parts[0], parts[1], parts[2], parts[3]))
+    }
+
+    /// Splits a BuyerPays sale into the same parts as split_sale. The winner escrowed
+    /// `premium` on top of the price, charged at `premium_basis_points`, so the seller
+    /// keeps all of `price` but royalties and the premium is split between the house
+    /// and the protocol. Each split adds up exactly, with ties for a leftover unit
+    /// going the way split_sale sends them.
+    pub fn split_premium_sale(
+        &self,
+        price: u64,
+        premium: u64,
+        premium_basis_points: u16,
+        royalty_basis_points: u16,
+    ) -> Result<(u64, u64, u64, u64)> {
+        let seller_basis_points = 10_000u16
+            .checked_sub(royalty_basis_points)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        // The house has no share of the price, so RemainderToTreasury falls back to
+        // split_sale's order, where royalties come before proceeds
+        let favoured = match self.rounding_policy {
+            RoundingPolicy::RemainderToSeller => 1,
+            RoundingPolicy::RemainderToTreasury => 0,
+        };
+        let parts = allocate_largest_remainder(
+            price,
+            &[royalty_basis_points as u64, seller_basis_points as u64],
+            favoured,
+        )?;
+
+        // The seller has no share of the premium, so the house fee wins its ties
+        // under either policy
+        let protocol_basis_points = self.protocol_fee_basis_points.min(premium_basis_points);
+        let house_basis_points = premium_basis_points
+            .checked_sub(protocol_basis_points)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        let fees = allocate_largest_remainder(
+            premium,
+            &[house_basis_points as u64, protocol_basis_points as u64],
+            0,
+        )?;
+
+        Ok((fees[0], fees[1], parts[0], parts[1]))
+    }
+
+    /// Part of the house fee on a sale price owed to a referrer
+    pub fn referral_fee_amount(&self, price: u64) -> Result<u64> {
+        basis_points_of(price, self.referral_fee_basis_points)
+    }
+}
+
+impl Auction {
+    pub const LEN: usize = 8
+        + 32
+        + 32
+        + 32
+        + 32
+        + 32
+        + 1
+        + 1
+        + 1
+        + 8
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 2
+        + 4
+        + 16 * MAX_INCREMENT_TIERS
+        + 8
+        + 8
+        + 8
+        + 3
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 33
+        + 33
+        + 2
+        + 1
+        + 33
+        + 1
+        + 8
+        + 8
+        + 1
+        + 1
+        + 4
+        + 32 * MAX_BUNDLE_MINTS
+        + 8
+        + 8
+        + 32
+        + MAX_AUCTION_NAME_LEN
+        + MAX_AUCTION_URI_LEN
+        + 1
+        + 1
+        + 2
+        + 1
+        + 1
+        + 1
+        + 48 * BID_HISTORY_LEN
+        + 1
+        + 32
+        + 2
+        + 32;
+
+    /// Account size under each versioned layout, from version 2 up to AUCTION_VERSION.
+    /// Each appended fields after version: bid_history and bid_history_head,
+    /// proceeds_destination, buyer_premium_basis_points, then original_authority.
+    pub const VERSIONED_LENS: [usize; AUCTION_VERSION as usize - 1] = [
+        Self::LEN - 48 * BID_HISTORY_LEN - 1 - 32 - 2 - 32,
+        Self::LEN - 32 - 2 - 32,
+        Self::LEN - 2 - 32,
+        Self::LEN - 32,
+        Self::LEN,
+    ];
+
+    /// Smallest raise over current_price; see increment_over
+    pub fn bid_increment(&self) -> Result<u64> {
+        self.increment_over(self.current_price)
+    }
+
+    /// Smallest raise over `price`, the largest of the fixed, percentage and tiered
+    /// increments
+    pub fn increment_over(&self, price: u64) -> Result<u64> {
+        let percentage_increment = (price as u128)
+            .checked_mul(self.min_bid_increment_bps as u128)
+            .and_then(|increment| increment.checked_div(10_000))
+            .and_then(|increment| u64::try_from(increment).ok())
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        // The tier containing `price` is the last one whose threshold it has reached
+        let tiered_increment = self
+            .increment_tiers
+            .iter()
+            .rev()
+            .find(|tier| price >= tier.threshold)
+            .map_or(0, |tier| tier.increment);
+
+        Ok(self
+            .min_bid_increment
+            .max(percentage_increment)
+            .max(tiered_increment))
+    }
+
+    /// Dutch auction price at `now`, declining linearly from start_price at start_time
+    /// to end_price at end_time and clamped to those bounds outside the window
+    pub fn dutch_price(&self, now: i64) -> Result<u64> {
+        if now <= self.start_time {
+            return Ok(self.start_price);
+        }
+        if now >= self.end_time {
+            return Ok(self.end_price);
+        }
+
+        let elapsed = now
+            .checked_sub(self.start_time)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        let duration = self
+            .end_time
+            .checked_sub(self.start_time)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        let price_range = self
+            .start_price
+            .checked_sub(self.end_price)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        let decline = (price_range as u128)
+            .checked_mul(elapsed as u128)
+            .and_then(|decline| decline.checked_div(duration as u128))
+            .and_then(|decline| u64::try_from(decline).ok())
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+        self.start_price
+            .checked_sub(decline)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Promotes a scheduled auction to Active once its start_time has passed
+    pub fn activate_if_started(&mut self, now: i64) {
+        if self.status == AuctionStatus::Pending && now >= self.start_time {
+            self.status = AuctionStatus::Active;
+        }
+    }
+
+    /// Time bidding closes for good: end_time, or the end of the reveal window for
+    /// sealed auctions
+    pub fn closes_at(&self) -> i64 {
+        if self.auction_type == AuctionType::SealedSecondPrice as u8 {
+            self.reveal_end_time
+        } else {
+            self.end_time
+        }
+    }
+
+    /// Time from which the auction can be settled, once the house settlement delay
+    /// after closes_at has passed
+    pub fn settles_at(&self, settlement_delay: i64) -> Result<i64> {
+        self.closes_at()
+            .checked_add(settlement_delay)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Whether end_auction would settle the auction at `now`
+    pub fn can_settle(&self, now: i64, settlement_delay: i64) -> Result<bool> {
+        Ok(self.is_open(now)
+            && !self.settled
+            && !self.frozen
+            && now >= self.settles_at(settlement_delay)?)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == AuctionStatus::Active
+    }
+
+    /// Active, or scheduled and not yet started: not yet over either way
+    pub fn is_open_or_pending(&self) -> bool {
+        self.is_active() || self.status == AuctionStatus::Pending
+    }
+
+    /// Fails unless `bidder` may retract their bid at `now`: only an unchallenged
+    /// leader may, while the auction runs. Once anyone else has bid, pulling the top
+    /// bid would let a bidder steer the price.
+    pub fn check_retraction(&self, bidder: &Pubkey, now: i64) -> Result<()> {
+        require!(self.is_active(), AuctionHouseError::AuctionNotActive);
+        require!(now < self.end_time, AuctionHouseError::AuctionEnded);
+        require!(
+            self.highest_bidder == Some(*bidder),
+            AuctionHouseError::NotHighestBidder
+        );
+        require!(
+            self.bid_count <= 1,
+            AuctionHouseError::BidRetractionNotAllowed
+        );
+        Ok(())
+    }
+
+    /// Reopens the auction at its minimum price after the leader retracts.
+    /// bid_count keeps counting, since it seeds the nonces of bids still to come.
+    pub fn reopen_after_retraction(&mut self) {
+        self.highest_bidder = None;
+        self.referrer = None;
+        self.winner_rebate_basis_points = 0;
+        self.current_price = self.minimum_price;
+        self.second_highest_bid = 0;
+        self.clear_bid_history();
+    }
+
+    /// Whether `now` falls within `window` seconds of created_at
+    pub fn in_cancel_grace_period(&self, now: i64, window: i64) -> Result<bool> {
+        let grace_end = self
+            .created_at
+            .checked_add(window)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        Ok(now < grace_end)
+    }
+
+    /// Fails unless extend_auction may move end_time out to `new_end_time`: no later
+    /// than max_end_time, which caps the seller as it caps bid extensions. The house
+    /// maximum still counts from when the auction was created.
+    pub fn check_extension(&self, new_end_time: i64, max_auction_duration: i64) -> Result<()> {
+        require!(
+            new_end_time > self.end_time,
+            AuctionHouseError::EndTimeNotExtended
+        );
+        require!(
+            self.max_end_time == 0 || new_end_time <= self.max_end_time,
+            AuctionHouseError::EndTimePastMax
+        );
+
+        if max_auction_duration > 0 {
+            let latest_end = self
+                .created_at
+                .checked_add(max_auction_duration)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+            require!(
+                new_end_time < latest_end,
+                AuctionHouseError::InvalidAuctionDuration
+            );
+        }
+        Ok(())
+    }
+
+    /// end_time after one bid extension, held at max_end_time once extensions reach it
+    pub fn extended_end_time(&self) -> Result<i64> {
+        let extended = self
+            .end_time
+            .checked_add(self.extension_amount)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        if self.max_end_time == 0 {
+            return Ok(extended);
+        }
+
+        Ok(extended.min(self.max_end_time).max(self.end_time))
+    }
+
+    /// Writes a bid into bid_history over the oldest entry
+    pub fn record_bid(&mut self, bidder: Pubkey, amount: u64, timestamp: i64) {
+        let head = self.bid_history_head as usize % BID_HISTORY_LEN;
+        self.bid_history[head] = BidRecord {
+            bidder,
+            amount,
+            timestamp,
+        };
+        self.bid_history_head = ((head + 1) % BID_HISTORY_LEN) as u8;
+    }
+
+    /// Empties bid_history when the auction reopens without bids
+    pub fn clear_bid_history(&mut self) {
+        self.bid_history = [BidRecord::default(); BID_HISTORY_LEN];
+        self.bid_history_head = 0;
+    }
+
+    /// Whether the NFTs only leave escrow through claim_nft: programmable NFTs need
+    /// the metadata program's transfer accounts and bundles an account triple per mint
+    pub fn held_until_claimed(&self) -> bool {
+        self.programmable || !self.bundle.is_empty()
+    }
+
+    /// Counts a payment into escrow towards escrowed_total
+    pub fn record_escrow_deposit(&mut self, amount: u64) -> Result<()> {
+        self.escrowed_total = self
+            .escrowed_total
+            .checked_add(amount)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Counts a payment out of escrow towards withdrawn_total
+    pub fn record_escrow_withdrawal(&mut self, amount: u64) -> Result<()> {
+        self.withdrawn_total = self
+            .withdrawn_total
+            .checked_add(amount)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Ended, cancelled or failed to meet its reserve; no further bids or settlement
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self.status,
+            AuctionStatus::Ended | AuctionStatus::Cancelled | AuctionStatus::ReserveNotMet
+        )
+    }
+
+    /// Whether min_bid_interval has passed since the last bid; the first bid is never
+    /// throttled
+    pub fn bid_interval_elapsed(&self, now: i64) -> Result<bool> {
+        if self.min_bid_interval == 0 || self.bid_count == 0 {
+            return Ok(true);
+        }
+
+        let next_bid_time = self
+            .last_bid_time
+            .checked_add(self.min_bid_interval)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        Ok(now >= next_bid_time)
+    }
+
+    /// Whether bidding is open at `now`, counting scheduled auctions that have started
+    pub fn can_bid(&self, now: i64) -> bool {
+        self.is_open(now) && now < self.end_time
+    }
+
+    /// Active, or scheduled with start_time already passed
+    fn is_open(&self, now: i64) -> bool {
+        self.is_active() || (self.status == AuctionStatus::Pending && now >= self.start_time)
+    }
+
+    /// Smallest bid place_bid will accept right now: above the opening price and at
+    /// least the opening bid, or one increment over the current bid once there is one,
+    /// and never below min_bid_raw
+    pub fn minimum_next_bid(&self) -> Result<u64> {
+        if self.highest_bidder.is_some() {
+            return Ok(self.next_bid_over(self.current_price)?.max(self.min_bid_raw));
+        }
+
+        let above_price = self
+            .current_price
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        Ok(above_price.max(self.opening_bid).max(self.min_bid_raw))
+    }
+
+    /// Smallest bid worth placing: minimum_next_bid, and while there are no bids also
+    /// at least the reserve, since a lower opening bid could never win
+    pub fn minimum_winning_bid(&self) -> Result<u64> {
+        let next_bid = self.minimum_next_bid()?.max(self.minimum_price);
+        if self.highest_bidder.is_none() {
+            return Ok(next_bid.max(self.reserve_price));
+        }
+
+        Ok(next_bid)
+    }
+
+    /// Lowest bid that beats `price` by a full increment, and by at least one unit
+    pub fn next_bid_over(&self, price: u64) -> Result<u64> {
+        price
+            .checked_add(self.increment_over(price)?.max(1))
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Status as of `now`: scheduled auctions count as Active once started, and
+    /// Active auctions past their end are reported as they would settle
+    pub fn effective_status(&self, now: i64) -> AuctionStatus {
+        let closes_at = self.closes_at();
+
+        if self.status == AuctionStatus::Pending && now >= self.start_time {
+            if now < closes_at {
+                return AuctionStatus::Active;
+            }
+        } else if self.status != AuctionStatus::Active || now < closes_at {
+            return self.status;
+        }
+
+        if self.highest_bidder.is_some() && self.current_price < self.reserve_price {
+            AuctionStatus::ReserveNotMet
+        } else {
+            AuctionStatus::Ended
+        }
+    }
+
+    /// Sealed auction settlement price: the second-highest revealed bid, raised to
+    /// minimum_price and, when the winning bid clears it, to reserve_price
+    pub fn sealed_clearing_price(&self) -> u64 {
+        let price = self.second_highest_bid.max(self.minimum_price);
+        if self.current_price >= self.reserve_price {
+            price.max(self.reserve_price)
+        } else {
+            price
+        }
+    }
+
+    /// Price an English SecondPrice winner pays: one increment over the runner-up,
+    /// the minimum price without one, raised to the reserve when the winning bid
+    /// meets it and never above the winning bid
+    pub fn second_price_clearing_price(&self) -> Result<u64> {
+        let mut price = if self.second_highest_bid > 0 {
+            self.next_bid_over(self.second_highest_bid)?.max(self.minimum_price)
+        } else {
+            self.minimum_price
+        };
+        if self.current_price >= self.reserve_price {
+            price = price.max(self.reserve_price);
+        }
+
+        Ok(price.min(self.current_price))
+    }
+
+    /// Whether `bid` was left by an earlier auction at this address, one closed by
+    /// sweep_closed and listed again or relisted. Bids land before their auction ends,
+    /// so such a record was last bid on before created_at. That auction was wound
+    /// down before this one opened, so of what the record holds only a bond a relist
+    /// kept in escrow is still owed.
+    pub fn is_stale_bid(&self, bid: &Bid) -> bool {
+        bid.timestamp < self.created_at
+    }
+
+    /// Amount `bid` has standing in this auction, zero for a stale record
+    pub fn standing_bid(&self, bid: &Bid) -> u64 {
+        if self.is_stale_bid(bid) {
+            return 0;
+        }
+        bid.amount
+    }
+
+    /// Escrow owed back to a bid once the auction is over, bond included. The winning
+    /// bid pays current_price, so its bidder only gets back collateral above that
+    /// price, and keeps their bond locked until they claim or it is forfeited.
+    pub fn refundable_amount(&self, bid: &Bid) -> Result<u64> {
+        let escrowed = self.escrow_for(self.standing_bid(bid))?;
+        if self.status == AuctionStatus::Ended && self.highest_bidder == Some(bid.bidder) {
+            // Withdrawing closes the bid record reclaim_bond needs
+            require!(
+                bid.bond == 0 || self.claimed,
+                AuctionHouseError::BidBondLocked
+            );
+            let surplus = escrowed
+                .saturating_sub(self.escrow_for(self.current_price)?)
+                .checked_add(bid.bond)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+            require!(surplus > 0, AuctionHouseError::WinningBidNotWithdrawable);
+            return Ok(surplus);
+        }
+
+        escrowed
+            .checked_add(bid.bond)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Whether a bid record can be closed without losing anything owed to its bidder:
+    /// the auction is final and no escrow or bond is left to withdraw
+    pub fn bid_closeable(&self, bid: &Bid) -> Result<bool> {
+        if !self.is_final() || bid.bond > 0 {
+            return Ok(false);
+        }
+
+        // The winner's escrow up to the price was paid to the seller at settlement
+        let escrowed = self.escrow_for(self.standing_bid(bid))?;
+        let is_winner =
+            self.status == AuctionStatus::Ended && self.highest_bidder == Some(bid.bidder);
+        let owed = if is_winner {
+            escrowed.saturating_sub(self.escrow_for(self.current_price)?)
+        } else {
+            escrowed
+        };
+        Ok(owed == 0)
+    }
+
+    /// Whether the auction account can be closed: verify_settlement has confirmed its
+    /// escrow is wound down and its seller slot is released. Bundle auctions keep an
+    /// NFT escrow per extra mint, so they are never swept.
+    pub fn closeable(&self) -> bool {
+        self.settlement_verified && !self.holds_seller_slot && self.bundle.is_empty()
+    }
+
+    /// House fee rate for this auction's sale: the override if any, capped at the
+    /// house default in case that has since been lowered
+    pub fn fee_basis_points(&self, auction_house: &AuctionHouse) -> u16 {
+        self.fee_override
+            .map_or(auction_house.seller_fee_basis_points, |fee_override| {
+                fee_override.min(auction_house.seller_fee_basis_points)
+            })
+    }
+
+    /// When reclaim_bond may forfeit an unclaiming winner's bond
+    pub fn bond_forfeits_at(&self, settlement_delay: i64) -> Result<i64> {
+        self.settles_at(settlement_delay)?
+            .checked_add(self.bond_claim_window)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Amount paid at `price`, which multi-unit auctions quote per unit
+    pub fn total_for(&self, price: u64) -> Result<u64> {
+        if !self.multi_unit {
+            return Ok(price);
+        }
+
+        price
+            .checked_mul(self.token_size)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Fees a BuyerPays winner pays on top of a sale amount, rounded down
+    pub fn buyer_premium(&self, amount: u64) -> Result<u64> {
+        basis_points_of(amount, self.buyer_premium_basis_points)
+    }
+
+    /// Escrow backing a bid at `price`: total_for plus the buyer premium
+    pub fn escrow_for(&self, price: u64) -> Result<u64> {
+        let total = self.total_for(price)?;
+        total
+            .checked_add(self.buyer_premium(total)?)
+            .ok_or_else(|| AuctionHouseError::ArithmeticOverflow.into())
+    }
+
+    /// Checked first by every entry point that bids or buys on behalf of `wallet`.
+    /// Moderators can halt a single auction without pausing the whole house, and
+    /// curated auctions only accept allowlisted wallets.
+    pub fn admission(&self, wallet: &Pubkey, proof: &[[u8; 32]]) -> BidCheck {
+        if self.frozen {
+            return BidCheck::AuctionFrozen;
+        }
+        if !self.is_allowlisted(wallet, proof) {
+            return BidCheck::BidderNotAllowlisted;
+        }
+        BidCheck::Accepted
+    }
+
+    /// Checks `proof` against allowlist_root. Leaves are sha256(wallet) and each level
+    /// hashes the sorted pair of nodes, so proofs carry no left/right flags.
+    pub fn is_allowlisted(&self, wallet: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        if self.allowlist_root == [0; 32] {
+            return true;
+        }
+
+        let mut node = hashv(&[wallet.as_ref()]).to_bytes();
+        for sibling in proof {
+            node = if node <= *sibling {
+                hashv(&[&node, sibling]).to_bytes()
+            } else {
+                hashv(&[sibling, &node]).to_bytes()
+            };
+        }
+
+        node == self.allowlist_root
+    }
+
+    /// Native SOL auctions escrow lamports in the auction account instead of SPL tokens
+    pub fn is_native(&self) -> bool {
+        self.treasury_mint == native_mint::ID
+    }
+}
+
+impl Bid {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 32 + 1;
+
+    /// Empties a stale record before it is bid on again, keeping any bond
+    pub fn clear_stale(&mut self) {
+        self.amount = 0;
+        self.max_bid = 0;
+        self.commitment = [0; 32];
+        self.revealed = false;
+    }
+}
+
+impl BidderEscrow {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 8 + 8 + 8 + 1;
+
+    pub fn is_native(&self) -> bool {
+        self.treasury_mint == native_mint::ID
+    }
+}
+
+impl RegisteredTreasuryMint {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 1 + 1 + 1;
+}
+
+impl SellerIndex {
+    pub const LEN: usize = 8 + 32 + 8 + 4 + 4 + 32 * MAX_SELLER_INDEX_AUCTIONS;
+
+    /// Appends a new auction, dropping the oldest entry once the list is full
+    pub fn record(&mut self, authority: Pubkey, auction: Pubkey) -> Result<()> {
+        self.authority = authority;
+        self.total_auctions = self
+            .total_auctions
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        if self.auctions.len() >= MAX_SELLER_INDEX_AUCTIONS {
+            self.auctions.remove(0);
+        }
+        self.auctions.push(auction);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, auction: &Pubkey) {
+        self.auctions.retain(|listed| listed != auction);
+    }
+
+    /// Counts `auction` as one of the seller's active auctions, failing once they
+    /// already have `max_active`; zero is unlimited
+    pub fn open_slot(&mut self, auction: &mut Auction, max_active: u32) -> Result<()> {
+        require!(
+            max_active == 0 || self.active_auctions < max_active,
+            AuctionHouseError::TooManyActiveAuctions
+        );
+        self.active_auctions = self
+            .active_auctions
+            .checked_add(1)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        auction.holds_seller_slot = true;
+        Ok(())
+    }
+
+    /// Frees the slot of an auction that has become final; anything else keeps its slot
+    pub fn release_slot(&mut self, auction: &mut Auction) {
+        if auction.holds_seller_slot && auction.is_final() {
+            self.active_auctions = self.active_auctions.saturating_sub(1);
+            auction.holds_seller_slot = false;
+        }
+    }
+}
+
+/// Reallocates an account written under an older layout to `len`, with `payer`
+/// topping up the rent the larger size needs
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    len: usize,
+) -> Result<()> {
+    let rent_due = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let transfer_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(transfer_ctx, rent_due)?;
+    }
+    account.realloc(len, true)?;
+    Ok(())
+}
+
+/// Overwrites an account's data with `value`, zeroing whatever an older layout
+/// left past its end
+fn write_account<T: AccountSerialize>(account: &AccountInfo, value: &T) -> Result<()> {
+    let mut data = account.try_borrow_mut_data()?;
+    data.fill(0);
+    value.try_serialize(&mut &mut data[..])
+}
+
+/// Frees the active slot of a final auction in its seller's index. Only auctions
+/// created before seller indexes existed hold no slot and may leave the index out.
+fn release_auction_slot(
+    seller_index: Option<&mut Account<'_, SellerIndex>>,
+    auction: &mut Auction,
+) -> Result<()> {
+    match seller_index {
+        Some(seller_index) => seller_index.release_slot(auction),
+        None => require!(
+            !auction.holds_seller_slot,
+            AuctionHouseError::MissingSellerIndex
+        ),
+    }
+    Ok(())
+}
+
+impl LoyaltyAccount {
+    pub const LEN: usize = 8 + 32 + 32 + 2 + 1;
+}
+
+impl Offer {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1;
+
+    pub fn is_native(&self) -> bool {
+        self.treasury_mint == native_mint::ID
+    }
+}
+
+impl Listing {
+    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 1;
+
+    pub fn is_native(&self) -> bool {
+        self.treasury_mint == native_mint::ID
+    }
+}
+
+/// Moves every bundled NFT out of its auction-owned escrow to `recipient`.
+/// `accounts` holds a (mint, escrow, destination) triple per auction.bundle entry,
+/// in order; auctions without a bundle take none.
+fn transfer_bundle<'info>(
+    auction: &Account<'info, Auction>,
+    accounts: &'info [AccountInfo<'info>],
+    recipient: &Pubkey,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    require!(
+        accounts.len() >= auction.bundle.len() * 3,
+        AuctionHouseError::InvalidBundleAccounts
+    );
+
+    for (bundle_mint, group) in auction.bundle.iter().zip(accounts.chunks(3)) {
+        let mint = InterfaceAccount::<Mint>::try_from(&group[0])?;
+        let escrow = InterfaceAccount::<TokenAccount>::try_from(&group[1])?;
+        let destination = InterfaceAccount::<TokenAccount>::try_from(&group[2])?;
+        require!(
+            mint.key() == *bundle_mint
+                && escrow.owner == auction.key()
+                && escrow.mint == *bundle_mint
+                && destination.owner == *recipient
+                && destination.mint == *bundle_mint,
+            AuctionHouseError::InvalidBundleAccounts
+        );
+
+        transfer_from_auction(auction, &escrow, &destination, &mint, token_program, 1)?;
+    }
+
+    Ok(())
+}
+
+/// Transfers tokens out of an account owned by the auction PDA, signing with its seeds.
+/// The auction account is the escrow authority, so no separate authority account is
+/// passed in and there is nothing for a caller to spoof.
+fn transfer_from_auction<'info>(
+    auction: &Account<'info, Auction>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let auction_seeds = &[
+        b"auction".as_ref(),
+        auction.token_mint.as_ref(),
+        auction.original_authority.as_ref(),
+        &[auction.bump],
+    ];
+    let signer_seeds = &[&auction_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: auction.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, mint.decimals)
+}
+
+/// Moves lamports held in the auction account to `to`, keeping the auction rent exempt
+fn transfer_lamports_from_auction<'info>(
+    auction: &Account<'info, Auction>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    transfer_lamports_from_program_account(&auction.to_account_info(), to, amount)
+}
+
+/// Moves lamports out of an account owned by this program, keeping it rent exempt
+fn transfer_lamports_from_program_account<'info>(
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let remaining = from
+        .lamports()
+        .checked_sub(amount)
+        .ok_or(AuctionHouseError::InsufficientFunds)?;
+    require!(
+        remaining >= Rent::get()?.minimum_balance(from.data_len()),
+        AuctionHouseError::InsufficientFunds
+    );
+    let to_balance = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    **from.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? = to_balance;
+
+    Ok(())
+}
+
+/// Moves a payment into escrow: lamports into the auction account for native SOL
+/// auctions, or tokens into the auction's treasury escrow otherwise
+#[allow(clippy::too_many_arguments)]
+fn deposit_to_escrow<'info>(
+    auction: &mut Account<'info, Auction>,
+    payer: &Signer<'info>,
+    payer_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if auction.is_native() {
+        let transfer_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: auction.to_account_info(),
+            },
+        );
+        system_program::transfer(transfer_ctx, amount)?;
+    } else {
+        let payer_token_account =
+            payer_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        let auction_token_account =
+            auction_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        let transfer_ctx = CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: payer_token_account.to_account_info(),
+                mint: treasury_mint.to_account_info(),
+                to: auction_token_account.to_account_info(),
+                authority: payer.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, treasury_mint.decimals)?;
+    }
+
+    auction.record_escrow_deposit(amount)
+}
+
+/// Pays out of escrow: lamports to `wallet` for native SOL auctions, or tokens to
+/// `token_account` otherwise. Callers are responsible for validating the recipient;
+/// only its mint is checked here.
+fn withdraw_from_escrow<'info>(
+    auction: &mut Account<'info, Auction>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    wallet: Option<AccountInfo<'info>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    if auction.is_native() {
+        let wallet = wallet.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        transfer_lamports_from_auction(auction, &wallet, amount)?;
+    } else {
+        let token_account = token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        require_treasury_mint(&auction.treasury_mint, treasury_mint, token_account)?;
+        transfer_from_auction(
+            auction,
+            auction_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?,
+            token_account,
+            treasury_mint,
+            token_program,
+            amount,
+        )?;
+    }
+
+    auction.record_escrow_withdrawal(amount)
+}
+
+/// Fails unless a payout's mint and the token account receiving it are both
+/// `expected`, the treasury mint of the escrow paying out
+fn require_treasury_mint(
+    expected: &Pubkey,
+    treasury_mint: &InterfaceAccount<'_, Mint>,
+    token_account: &InterfaceAccount<'_, TokenAccount>,
+) -> Result<()> {
+    require!(
+        treasury_mint.key() == *expected && token_account.mint == *expected,
+        AuctionHouseError::MintMismatch
+    );
+    Ok(())
+}
+
+/// Whether the auction's treasury escrow holds anything: lamports above rent for
+/// native SOL auctions, or a token balance otherwise
+fn escrow_holds_funds<'info>(
+    auction: &Account<'info, Auction>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+) -> Result<bool> {
+    if auction.is_native() {
+        let rent_exempt = Rent::get()?.minimum_balance(auction.to_account_info().data_len());
+        return Ok(auction.to_account_info().lamports() > rent_exempt);
+    }
+
+    let escrow = auction_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+    Ok(escrow.amount > 0)
+}
+
+/// Returns the escrowed funds of the auction's current highest bidder, if it has one
+fn refund_highest_bidder<'info>(
+    auction: &mut Account<'info, Auction>,
+    previous_bid: Option<&mut Account<'info, Bid>>,
+    previous_bidder_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    previous_bidder_wallet: Option<AccountInfo<'info>>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let previous_bidder = match auction.highest_bidder {
+        Some(previous_bidder) => previous_bidder,
+        None => return Ok(()),
+    };
+    let previous_bid = previous_bid.ok_or(AuctionHouseError::MissingPreviousBidder)?;
+
+    require!(
+        previous_bid.auction == auction.key() && previous_bid.bidder == previous_bidder,
+        AuctionHouseError::InvalidPreviousBidder
+    );
+    if let Some(token_account) = previous_bidder_token_account {
+        require!(
+            token_account.owner == previous_bidder,
+            AuctionHouseError::InvalidPreviousBidder
+        );
+    }
+    if let Some(wallet) = &previous_bidder_wallet {
+        require!(
+            wallet.key() == previous_bidder,
+            AuctionHouseError::InvalidPreviousBidder
+        );
+    }
+
+    let refund = auction.escrow_for(previous_bid.amount)?;
+    withdraw_from_escrow(
+        auction,
+        auction_token_account,
+        previous_bidder_token_account,
+        previous_bidder_wallet,
+        treasury_mint,
+        token_program,
+        refund,
+    )?;
+    previous_bid.amount = 0;
+    previous_bid.max_bid = 0;
+
+    emit!(BidRefunded {
+        auction: auction.key(),
+        bidder: previous_bidder,
+        amount: refund,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+/// Transfers tokens out of an account owned by the auction house PDA, signing with its seeds
+fn transfer_from_auction_house<'info>(
+    auction_house: &Account<'info, AuctionHouse>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let auction_house_seeds = &[
+        b"auction_house".as_ref(),
+        auction_house.creator.as_ref(),
+        &[auction_house.bump],
+    ];
+    let signer_seeds = &[&auction_house_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: from.to_account_info(),
+            mint: treasury_mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: auction_house.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, treasury_mint.decimals)
+}
+
+/// Transfers tokens out of a listing-owned escrow, signing with the listing's seeds
+fn transfer_from_listing<'info>(
+    listing: &Account<'info, Listing>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    let listing_seeds = &[
+        b"listing".as_ref(),
+        listing.token_mint.as_ref(),
+        listing.authority.as_ref(),
+        &[listing.bump],
+    ];
+    let signer_seeds = &[&listing_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: from.to_account_info(),
+            mint: mint.to_account_info(),
+            to: to.to_account_info(),
+            authority: listing.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, mint.decimals)
+}
+
+/// Closes the emptied NFT escrow of a listing, returning its rent to `destination`
+fn close_listing_escrow<'info>(
+    listing: &Account<'info, Listing>,
+    escrow: &InterfaceAccount<'info, TokenAccount>,
+    destination: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let listing_seeds = &[
+        b"listing".as_ref(),
+        listing.token_mint.as_ref(),
+        listing.authority.as_ref(),
+        &[listing.bump],
+    ];
+    let signer_seeds = &[&listing_seeds[..]];
+    let close_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: escrow.to_account_info(),
+            destination,
+            authority: listing.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::close_account(close_ctx)
+}
+
+/// Whether a seller's NFT account can be escrowed cleanly: no delegate, and not
+/// frozen unless it holds a programmable NFT, which the metadata program keeps frozen
+fn is_transferable(token_account: &TokenAccount, programmable: bool) -> bool {
+    token_account.delegate.is_none() && (programmable || !token_account.is_frozen())
+}
+
+/// Closes an emptied auction-owned NFT escrow, sending its rent to `destination`
+fn close_auction_escrow<'info>(
+    auction: &Account<'info, Auction>,
+    escrow: &InterfaceAccount<'info, TokenAccount>,
+    destination: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<()> {
+    let auction_seeds = &[
+        b"auction".as_ref(),
+        auction.token_mint.as_ref(),
+        auction.original_authority.as_ref(),
+        &[auction.bump],
+    ];
+    let signer_seeds = &[&auction_seeds[..]];
+    let close_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: escrow.to_account_info(),
+            destination,
+            authority: auction.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::close_account(close_ctx)
+}
+
+/// Pays straight from a signing payer: lamports to `wallet` for native SOL, or
+/// tokens to `token_account` otherwise. Callers are responsible for validating the
+/// recipient.
+#[allow(clippy::too_many_arguments)]
+fn pay_from_signer<'info>(
+    native: bool,
+    payer: &Signer<'info>,
+    payer_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    wallet: Option<AccountInfo<'info>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if native {
+        let transfer_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: wallet.ok_or(AuctionHouseError::MissingPaymentAccount)?,
+            },
+        );
+
+        return system_program::transfer(transfer_ctx, amount);
+    }
+
+    let transfer_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: payer_token_account
+                .ok_or(AuctionHouseError::MissingPaymentAccount)?
+                .to_account_info(),
+            mint: treasury_mint.to_account_info(),
+            to: token_account
+                .ok_or(AuctionHouseError::MissingPaymentAccount)?
+                .to_account_info(),
+            authority: payer.to_account_info(),
+        },
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, treasury_mint.decimals)
+}
+
+/// Pays out of a bidder escrow: lamports held in the escrow account to `wallet` for
+/// native SOL, or tokens from the escrow-owned `escrow_token_account` to
+/// `token_account` otherwise. Callers are responsible for validating the recipient;
+/// only its mint is checked here.
+fn withdraw_from_bidder_escrow<'info>(
+    bidder_escrow: &Account<'info, BidderEscrow>,
+    escrow_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    wallet: Option<AccountInfo<'info>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    if bidder_escrow.is_native() {
+        let wallet = wallet.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        return transfer_lamports_from_program_account(
+            &bidder_escrow.to_account_info(),
+            &wallet,
+            amount,
+        );
+    }
+
+    let escrow_token_account =
+        escrow_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+    require!(
+        escrow_token_account.owner == bidder_escrow.key(),
+        AuctionHouseError::MissingPaymentAccount
+    );
+    let token_account = token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+    require_treasury_mint(&bidder_escrow.treasury_mint, treasury_mint, token_account)?;
+    let escrow_seeds = &[
+        b"bidder_escrow".as_ref(),
+        bidder_escrow.auction_house.as_ref(),
+        bidder_escrow.bidder.as_ref(),
+        &[bidder_escrow.bump],
+    ];
+    let signer_seeds = &[&escrow_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: escrow_token_account.to_account_info(),
+            mint: treasury_mint.to_account_info(),
+            to: token_account.to_account_info(),
+            authority: bidder_escrow.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, treasury_mint.decimals)
+}
+
+/// Moves part of a bidder escrow's available balance into an auction's bid escrow
+fn draw_from_bidder_escrow<'info>(
+    bidder_escrow: &mut Account<'info, BidderEscrow>,
+    escrow_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    auction: &mut Account<'info, Auction>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    bidder_escrow.available = bidder_escrow
+        .available
+        .checked_sub(amount)
+        .ok_or(AuctionHouseError::InsufficientFunds)?;
+    bidder_escrow.committed = bidder_escrow
+        .committed
+        .checked_add(amount)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    withdraw_from_bidder_escrow(
+        bidder_escrow,
+        escrow_token_account,
+        auction_token_account,
+        Some(auction.to_account_info()),
+        treasury_mint,
+        token_program,
+        amount,
+    )?;
+
+    auction.record_escrow_deposit(amount)
+}
+
+/// Pays out of an offer's escrow: lamports held in the offer account to `wallet` for
+/// native SOL, or tokens from the offer-owned `offer_escrow` to `token_account`
+/// otherwise. Callers are responsible for validating the recipient; only its mint is
+/// checked here.
+fn withdraw_from_offer<'info>(
+    offer: &Account<'info, Offer>,
+    offer_escrow: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    wallet: Option<AccountInfo<'info>>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    if offer.is_native() {
+        let wallet = wallet.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        return transfer_lamports_from_program_account(&offer.to_account_info(), &wallet, amount);
+    }
+
+    let token_account = token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+    require_treasury_mint(&offer.treasury_mint, treasury_mint, token_account)?;
+
+    let offer_seeds = &[
+        b"offer".as_ref(),
+        offer.token_mint.as_ref(),
+        offer.offerer.as_ref(),
+        &[offer.bump],
+    ];
+    let signer_seeds = &[&offer_seeds[..]];
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: offer_escrow
+                .ok_or(AuctionHouseError::MissingPaymentAccount)?
+                .to_account_info(),
+            mint: treasury_mint.to_account_info(),
+            to: token_account.to_account_info(),
+            authority: offer.to_account_info(),
+        },
+        signer_seeds,
+    );
+
+    token_interface::transfer_checked(transfer_ctx, amount, treasury_mint.decimals)
+}
+
+/// Creator entry of a Metaplex metadata account
+#[derive(AnchorDeserialize)]
+struct MetadataCreator {
+    address: Pubkey,
+    _verified: bool,
+    share: u8,
+}
+
+/// Leading fields of a Metaplex metadata account, enough to read its royalty terms
+/// without a CPI or a dependency on the metadata program crate
+#[derive(AnchorDeserialize)]
+struct MetadataRoyalties {
+    _key: u8,
+    _update_authority: Pubkey,
+    mint: Pubkey,
+    _name: String,
+    _symbol: String,
+    _uri: String,
+    seller_fee_basis_points: u16,
+    creators: Option<Vec<MetadataCreator>>,
+}
+
+/// Collection entry of a Metaplex metadata account
+#[derive(AnchorDeserialize)]
+struct MetadataCollection {
+    verified: bool,
+    key: Pubkey,
+}
+
+/// Metaplex metadata fields up to the collection, describing what kind of asset a
+/// mint is and which collection it belongs to
+#[derive(AnchorDeserialize)]
+struct MetadataAsset {
+    _key: u8,
+    _update_authority: Pubkey,
+    mint: Pubkey,
+    _name: String,
+    _symbol: String,
+    _uri: String,
+    _seller_fee_basis_points: u16,
+    _creators: Option<Vec<MetadataCreator>>,
+    _primary_sale_happened: bool,
+    _is_mutable: bool,
+    _edition_nonce: Option<u8>,
+    token_standard: Option<u8>,
+    collection: Option<MetadataCollection>,
+}
+
+impl MetadataAsset {
+    fn is_programmable(&self) -> bool {
+        self.token_standard == Some(TOKEN_STANDARD_PROGRAMMABLE_NON_FUNGIBLE)
+    }
+}
+
+/// Token accounts and signers of a programmable NFT transfer
+struct ProgrammableTransfer<'info> {
+    source: AccountInfo<'info>,
+    source_owner: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    destination_owner: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    payer: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+}
+
+/// Moves a programmable NFT with the token metadata program's Transfer, which checks
+/// the NFT's rule set and maintains its token records. `accounts` holds the
+/// PROGRAMMABLE_TRANSFER_ACCOUNTS remaining accounts the transfer needs.
+fn transfer_programmable<'info>(
+    accounts: &[AccountInfo<'info>],
+    transfer: &ProgrammableTransfer<'info>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    require!(
+        accounts.len() >= PROGRAMMABLE_TRANSFER_ACCOUNTS,
+        AuctionHouseError::MissingProgrammableAccounts
+    );
+    let metadata = &accounts[0];
+    let edition = &accounts[1];
+    let owner_token_record = &accounts[2];
+    let destination_token_record = &accounts[3];
+    let authorization_rules_program = &accounts[4];
+    let authorization_rules = &accounts[5];
+    let instructions = &accounts[6];
+    let system_program = &accounts[7];
+    let associated_token_program = &accounts[8];
+    let metadata_program = &accounts[9];
+
+    // The metadata program validates the edition, token records and rule set itself
+    let (expected_metadata, _) = Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM_ID.as_ref(), transfer.mint.key.as_ref()],
+        &METADATA_PROGRAM_ID,
+    );
+    require!(
+        metadata.key() == expected_metadata
+            && instructions.key() == sysvar::instructions::ID
+            && system_program.key() == system_program::ID
+            && associated_token_program.key() == ASSOCIATED_TOKEN_PROGRAM_ID
+            && metadata_program.key() == METADATA_PROGRAM_ID,
+        AuctionHouseError::MissingProgrammableAccounts
+    );
+
+    // TransferArgs::V1 { amount, authorization_data: None }
+    let mut data = vec![METADATA_TRANSFER_INSTRUCTION, 0];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(0);
+
+    let instruction = Instruction {
+        program_id: METADATA_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(transfer.source.key(), false),
+            AccountMeta::new_readonly(transfer.source_owner.key(), false),
+            AccountMeta::new(transfer.destination.key(), false),
+            AccountMeta::new_readonly(transfer.destination_owner.key(), false),
+            AccountMeta::new_readonly(transfer.mint.key(), false),
+            AccountMeta::new(metadata.key(), false),
+            AccountMeta::new_readonly(edition.key(), false),
+            AccountMeta::new(owner_token_record.key(), false),
+            AccountMeta::new(destination_token_record.key(), false),
+            AccountMeta::new_readonly(transfer.authority.key(), true),
+            AccountMeta::new(transfer.payer.key(), true),
+            AccountMeta::new_readonly(system_program.key(), false),
+            AccountMeta::new_readonly(instructions.key(), false),
+            AccountMeta::new_readonly(transfer.token_program.key(), false),
+            AccountMeta::new_readonly(associated_token_program.key(), false),
+            AccountMeta::new_readonly(authorization_rules_program.key(), false),
+            AccountMeta::new_readonly(authorization_rules.key(), false),
+        ],
+        data,
+    };
+
+    invoke_signed(
+        &instruction,
+        &[
+            transfer.source.clone(),
+            transfer.source_owner.clone(),
+            transfer.destination.clone(),
+            transfer.destination_owner.clone(),
+            transfer.mint.clone(),
+            metadata.clone(),
+            edition.clone(),
+            owner_token_record.clone(),
+            destination_token_record.clone(),
+            transfer.authority.clone(),
+            transfer.payer.clone(),
+            system_program.clone(),
+            instructions.clone(),
+            transfer.token_program.clone(),
+            associated_token_program.clone(),
+            authorization_rules_program.clone(),
+            authorization_rules.clone(),
+            metadata_program.clone(),
+        ],
+        signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Reads the asset fields of `mint` from its metadata account, or None if the mint
+/// has no metadata. The account address itself is checked by the seeds constraint.
+fn read_metadata_asset(metadata: &AccountInfo, mint: &Pubkey) -> Result<Option<MetadataAsset>> {
+    if metadata.owner != &METADATA_PROGRAM_ID || metadata.data_is_empty() {
+        return Ok(None);
+    }
+
+    let data = metadata.try_borrow_data()?;
+    let asset = MetadataAsset::deserialize(&mut &data[..])
+        .map_err(|_| AuctionHouseError::InvalidMetadata)?;
+    require!(asset.mint == *mint, AuctionHouseError::InvalidMetadata);
+
+    Ok(Some(asset))
+}
+
+/// Reads the royalty terms of `mint` from its metadata account, or None if the mint
+/// has no metadata. The account address itself is checked by the seeds constraint.
+fn read_metadata_royalties(
+    metadata: &AccountInfo,
+    mint: &Pubkey,
+) -> Result<Option<MetadataRoyalties>> {
+    if metadata.owner != &METADATA_PROGRAM_ID || metadata.data_is_empty() {
+        return Ok(None);
+    }
+
+    let data = metadata.try_borrow_data()?;
+    let royalties = MetadataRoyalties::deserialize(&mut &data[..])
+        .map_err(|_| AuctionHouseError::InvalidMetadata)?;
+    require!(
+        royalties.mint == *mint && royalties.seller_fee_basis_points <= 10_000,
+        AuctionHouseError::InvalidMetadata
+    );
+
+    Ok(Some(royalties))
+}
+
+/// Pays `royalties` out of escrow to the creators, split by their shares with
+/// allocate_largest_remainder, and returns the total paid. `creator_accounts` holds
+/// one payment account per creator, in metadata order: a treasury mint token account,
+/// or the creator's wallet for native SOL auctions.
+fn pay_creator_royalties<'info>(
+    auction: &mut Account<'info, Auction>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    creators: &[MetadataCreator],
+    creator_accounts: &'info [AccountInfo<'info>],
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    royalties: u64,
+) -> Result<u64> {
+    require!(
+        creator_accounts.len() >= creators.len(),
+        AuctionHouseError::MissingPaymentAccount
+    );
+
+    let weights: Vec<u64> = creators.iter().map(|creator| creator.share as u64).collect();
+    let shares = allocate_largest_remainder(royalties, &weights, 0)?;
+
+    let mut paid: u64 = 0;
+    for ((creator, creator_account), share) in creators.iter().zip(creator_accounts).zip(shares) {
+        if share == 0 {
+            continue;
+        }
+
+        if auction.is_native() {
+            require!(
+                creator_account.key() == creator.address,
+                AuctionHouseError::InvalidCreatorAccount
+            );
+            transfer_lamports_from_auction(auction, creator_account, share)?;
+        } else {
+            let token_account = InterfaceAccount::<TokenAccount>::try_from(creator_account)?;
+            require!(
+                token_account.owner == creator.address
+                    && token_account.mint == auction.treasury_mint,
+                AuctionHouseError::InvalidCreatorAccount
+            );
+            transfer_from_auction(
+                auction,
+                auction_token_account.ok_or(AuctionHouseError::MissingPaymentAccount)?,
+                &token_account,
+                treasury_mint,
+                token_program,
+                share,
+            )?;
+        }
+
+        auction.record_escrow_withdrawal(share)?;
+        emit!(RoyaltyPaid {
+            auction: auction.key(),
+            creator: creator.address,
+            amount: share,
+        });
+        paid = paid
+            .checked_add(share)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+    }
+
+    Ok(paid)
+}
+
+/// Pays a sale price out of the bid escrow, sending the house fee to the treasury,
+/// creator royalties to the NFT's creators and the remainder to the seller. Under
+/// BuyerPays the fees come out of the buyer premium escrowed on top of `price`.
+#[allow(clippy::too_many_arguments)]
+fn pay_out_sale<'info>(
+    auction: &mut Account<'info, Auction>,
+    auction_house: &Account<'info, AuctionHouse>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    auction_house_treasury: &InterfaceAccount<'info, TokenAccount>,
+    protocol_fee_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    seller_proceeds_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    seller: Option<AccountInfo<'info>>,
+    referrer_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    referrer: Option<AccountInfo<'info>>,
+    winner_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    winner: Option<AccountInfo<'info>>,
+    metadata: &AccountInfo<'info>,
+    creator_accounts: &'info [AccountInfo<'info>],
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    price: u64,
+) -> Result<()> {
+    let metadata_royalties = read_metadata_royalties(metadata, &auction.token_mint)?;
+    let royalty_basis_points = metadata_royalties
+        .as_ref()
+        .map_or(0, |royalties| royalties.seller_fee_basis_points);
+    let (fee, protocol_fee, royalties, proceeds) = if auction.buyer_premium_basis_points > 0 {
+        auction_house.split_premium_sale(
+            price,
+            auction.buyer_premium(price)?,
+            auction.buyer_premium_basis_points,
+            royalty_basis_points,
+        )?
+    } else {
+        auction_house.split_sale(
+            price,
+            auction.fee_basis_points(auction_house),
+            royalty_basis_points,
+        )?
+    };
+
+    let creators = metadata_royalties
+        .and_then(|royalties| royalties.creators)
+        .unwrap_or_default();
+    let paid = pay_creator_royalties(
+        auction,
+        auction_token_account,
+        &creators,
+        creator_accounts,
+        treasury_mint,
+        token_program,
+        royalties,
+    )?;
+
+    // Royalties no creator is listed to receive stay with the seller
+    let proceeds = royalties
+        .checked_sub(paid)
+        .and_then(|unpaid| unpaid.checked_add(proceeds))
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    // A referred sale routes part of the house fee to the referrer, never more than
+    // a reduced fee override leaves
+    let referral_fee = match auction.referrer {
+        Some(_) => auction_house.referral_fee_amount(price)?.min(fee),
+        None => 0,
+    };
+    let house_fee = fee
+        .checked_sub(referral_fee)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    // Loyalty rebates are a share of the fee the house keeps, so they can never
+    // exceed what was collected
+    let rebate = basis_points_of(house_fee, auction.winner_rebate_basis_points)?;
+    let treasury_fee = house_fee
+        .checked_sub(rebate)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    if treasury_fee > 0 {
+        pay_fee_from_escrow(
+            auction,
+            auction_token_account,
+            auction_house_treasury,
+            treasury_mint,
+            token_program,
+            treasury_fee,
+        )?;
+        emit!(FeePaid {
+            sale: auction.key(),
+            kind: FeeKind::House,
+            recipient: auction_house_treasury.key(),
+            amount: treasury_fee,
+        });
+    }
+    if referral_fee > 0 {
+        let referrer_key = auction.referrer.ok_or(AuctionHouseError::InvalidReferrer)?;
+        if let Some(token_account) = referrer_token_account {
+            require!(
+                Some(token_account.owner) == auction.referrer,
+                AuctionHouseError::InvalidReferrer
+            );
+        }
+        if let Some(wallet) = &referrer {
+            require!(
+                Some(wallet.key()) == auction.referrer,
+                AuctionHouseError::InvalidReferrer
+            );
+        }
+        withdraw_from_escrow(
+            auction,
+            auction_token_account,
+            referrer_token_account,
+            referrer,
+            treasury_mint,
+            token_program,
+            referral_fee,
+        )?;
+        emit!(FeePaid {
+            sale: auction.key(),
+            kind: FeeKind::Referral,
+            recipient: referrer_key,
+            amount: referral_fee,
+        });
+    }
+    if rebate > 0 {
+        let winner_key = auction
+            .highest_bidder
+            .ok_or(AuctionHouseError::InvalidWinnerAccount)?;
+        if let Some(token_account) = winner_token_account {
+            require!(
+                Some(token_account.owner) == auction.highest_bidder,
+                AuctionHouseError::InvalidWinnerAccount
+            );
+        }
+        if let Some(wallet) = &winner {
+            require!(
+                Some(wallet.key()) == auction.highest_bidder,
+                AuctionHouseError::InvalidWinnerAccount
+            );
+        }
+        withdraw_from_escrow(
+            auction,
+            auction_token_account,
+            winner_token_account,
+            winner,
+            treasury_mint,
+            token_program,
+            rebate,
+        )?;
+        emit!(FeePaid {
+            sale: auction.key(),
+            kind: FeeKind::Rebate,
+            recipient: winner_key,
+            amount: rebate,
+        });
+    }
+    if protocol_fee > 0 {
+        let protocol_fee_account =
+            protocol_fee_account.ok_or(AuctionHouseError::MissingPaymentAccount)?;
+        pay_fee_from_escrow(
+            auction,
+            auction_token_account,
+            protocol_fee_account,
+            treasury_mint,
+            token_program,
+            protocol_fee,
+        )?;
+        emit!(FeePaid {
+            sale: auction.key(),
+            kind: FeeKind::Protocol,
+            recipient: protocol_fee_account.key(),
+            amount: protocol_fee,
+        });
+    }
+
+    withdraw_from_escrow(
+        auction,
+        auction_token_account,
+        seller_proceeds_account,
+        seller,
+        treasury_mint,
+        token_program,
+        proceeds,
+    )
+}
+
+/// Pays a fee out of escrow into a treasury mint token account, which native SOL
+/// houses keep in wrapped SOL
+fn pay_fee_from_escrow<'info>(
+    auction: &mut Account<'info, Auction>,
+    auction_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    destination: &InterfaceAccount<'info, TokenAccount>,
+    treasury_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    amount: u64,
+) -> Result<()> {
+    withdraw_from_escrow(
+        auction,
+        auction_token_account,
+        Some(destination),
+        Some(destination.to_account_info()),
+        treasury_mint,
+        token_program,
+        amount,
+    )?;
+
+    // Lamports moved into a wrapped SOL account only count once synced
+    if auction.is_native() {
+        sync_wrapped_sol(token_program, destination)?;
+    }
+
+    Ok(())
+}
+
+/// Brings a wrapped SOL token account's balance in line with its lamports
+fn sync_wrapped_sol<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    account: &InterfaceAccount<'info, TokenAccount>,
+) -> Result<()> {
+    let sync_ctx = CpiContext::new(
+        token_program.to_account_info(),
+        SyncNative {
+            account: account.to_account_info(),
+        },
+    );
+
+    token_interface::sync_native(sync_ctx)
+}
+
+/// `value` as UTF-8 bytes padded with zeros to `N`, failing if it does not fit
+fn padded_bytes<const N: usize>(value: &str) -> Result<[u8; N]> {
+    require!(value.len() <= N, AuctionHouseError::AuctionMetadataTooLong);
+
+    let mut bytes = [0u8; N];
+    bytes[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(bytes)
+}
+
+/// `basis_points` of `amount`, rounded down
+fn basis_points_of(amount: u64, basis_points: u16) -> Result<u64> {
+    let share = (amount as u128)
+        .checked_mul(basis_points as u128)
+        .and_then(|share| share.checked_div(10_000))
+        .and_then(|share| u64::try_from(share).ok())
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    Ok(share)
+}
+
+/// Splits `gross` in proportion to `weights` by the largest remainder method: every
+/// part is rounded down, then the units left over go one each to the parts with the
+/// largest remainders, ties going to `favoured` and then the earliest part. The parts
+/// add up to `gross` exactly, or are all zero when every weight is.
+fn allocate_largest_remainder(gross: u64, weights: &[u64], favoured: usize) -> Result<Vec<u64>> {
+    let total_weight = weights
+        .iter()
+        .try_fold(0u128, |total, &weight| total.checked_add(weight as u128))
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+    if total_weight == 0 {
+        return Ok(vec![0; weights.len()]);
+    }
+
+    let mut parts = Vec::with_capacity(weights.len());
+    let mut remainders = Vec::with_capacity(weights.len());
+    let mut allocated: u128 = 0;
+    for &weight in weights {
+        let scaled = (gross as u128)
+            .checked_mul(weight as u128)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        let part = scaled / total_weight;
+        parts.push(part);
+        remainders.push(scaled % total_weight);
+        allocated = allocated
+            .checked_add(part)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+    }
+
+    // Fewer units are left over than there are parts with a remainder, so no part
+    // gets more than one
+    let leftover = (gross as u128)
+        .checked_sub(allocated)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by_key(|&index| (Reverse(remainders[index]), index != favoured, index));
+    for &index in order.iter().take(leftover as usize) {
+        parts[index] += 1;
+    }
+
+    parts
+        .into_iter()
+        .map(|part| u64::try_from(part).map_err(|_| AuctionHouseError::ArithmeticOverflow.into()))
+        .collect()
+}
+
+/// The house and protocol fees together never take more than the sale price
+fn valid_fee_split(seller_fee_basis_points: u16, protocol_fee_basis_points: u16) -> bool {
+    (seller_fee_basis_points as u32) + (protocol_fee_basis_points as u32) <= 10_000
+}
+
+/// Whether an increment table is small enough to store, with strictly ascending
+/// thresholds and no zero increments
+fn valid_increment_tiers(tiers: &[IncrementTier]) -> bool {
+    tiers.len() <= MAX_INCREMENT_TIERS
+        && tiers.iter().all(|tier| tier.increment > 0)
+        && tiers
+            .windows(2)
+            .all(|pair| pair[0].threshold < pair[1].threshold)
+}
+
+/// Duration bounds are non-negative, and the minimum fits under any maximum
+fn valid_duration_bounds(min_auction_duration: i64, max_auction_duration: i64) -> bool {
+    min_auction_duration >= 0
+        && max_auction_duration >= 0
+        && (max_auction_duration == 0 || min_auction_duration < max_auction_duration)
+}
+
+/// Accounts a bid is validated against and escrowed through, shared by place_bid
+/// and place_bid_with_sig
+struct BidAccounts<'a, 'info> {
+    auction: &'a mut Account<'info, Auction>,
+    auction_house: &'a Account<'info, AuctionHouse>,
+    bid: &'a mut Account<'info, Bid>,
+    bidder: Pubkey,
+    /// Signing bidder wallet, paying for the bid when there is no bidder_escrow
+    bidder_wallet: Option<&'a Signer<'info>>,
+    bidder_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    auction_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    previous_bid: Option<&'a mut Account<'info, Bid>>,
+    previous_bidder_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    previous_bidder: Option<AccountInfo<'info>>,
+    treasury_mint: &'a InterfaceAccount<'info, Mint>,
+    referrer: Option<Pubkey>,
+    loyalty: Option<&'a Account<'info, LoyaltyAccount>>,
+    bidder_escrow: Option<&'a mut Account<'info, BidderEscrow>>,
+    bidder_escrow_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    notify_program: Option<AccountInfo<'info>>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    system_program: &'a Program<'info, System>,
+}
+
+/// Sends OUTBID_NOTIFICATION_TAG, the auction, the outbid bidder and the new price to
+/// a house's notify_program. No accounts are passed, least of all the auction as a
+/// signer, so the external program can read the message but act on nothing of ours.
+fn notify_outbid(
+    notify_program: &AccountInfo,
+    auction: &Pubkey,
+    outbid: &Pubkey,
+    price: u64,
+) -> Result<()> {
+    let mut data = Vec::with_capacity(8 + 32 + 32 + 8);
+    data.extend_from_slice(&OUTBID_NOTIFICATION_TAG);
+    data.extend_from_slice(auction.as_ref());
+    data.extend_from_slice(outbid.as_ref());
+    data.extend_from_slice(&price.to_le_bytes());
+
+    let instruction = Instruction {
+        program_id: notify_program.key(),
+        accounts: vec![],
+        data,
+    };
+    invoke(&instruction, std::slice::from_ref(notify_program))?;
+    Ok(())
+}
+
+/// Runs place_bid's checks on a prospective bid from `bidder`, whose standing bid is
+/// `standing_bid`, without touching any account
+#[allow(clippy::too_many_arguments)]
+fn check_bid(
+    auction_house: &AuctionHouse,
+    auction: &Auction,
+    bidder: &Pubkey,
+    standing_bid: u64,
+    bid_amount: u64,
+    max_bid: u64,
+    proof: &[[u8; 32]],
+    now: i64,
+) -> Result<BidCheck> {
+    if auction_house.paused {
+        return Ok(BidCheck::AuctionHousePaused);
+    }
+
+    let admission = auction.admission(bidder, proof);
+    if admission != BidCheck::Accepted {
+        return Ok(admission);
+    }
+
+    // Scheduled auctions open for bidding at start_time
+    if auction.status == AuctionStatus::Pending && now < auction.start_time {
+        return Ok(BidCheck::AuctionNotStarted);
+    }
+    if !auction.is_open(now) {
+        return Ok(BidCheck::AuctionNotActive);
+    }
+    if now >= auction.end_time {
+        return Ok(BidCheck::AuctionEnded);
+    }
+
+    // Throttle bids that follow the previous one too closely, whoever placed it
+    if !auction.bid_interval_elapsed(now)? {
+        return Ok(BidCheck::BiddingTooFast);
+    }
+
+    // Dutch auctions are sold through buy_dutch and sealed auctions through commit_bid
+    if auction.auction_type != AuctionType::EnglishAscending as u8 {
+        return Ok(BidCheck::WrongAuctionType);
+    }
+
+    // Sellers may not bid up their own listing unless the house allows it
+    if !auction_house.allow_self_bid && *bidder == auction.authority {
+        return Ok(BidCheck::SelfBiddingNotAllowed);
+    }
+
+    // A proxy ceiling lets the program bid on the bidder's behalf up to max_bid;
+    // zero bids exactly bid_amount
+    if max_bid != 0 && max_bid < bid_amount {
+        return Ok(BidCheck::InvalidMaxBid);
+    }
+
+    // Amounts past the house ceiling are far more likely typos than intended bids
+    if max_bid.max(bid_amount) > auction_house.absolute_max_bid {
+        return Ok(BidCheck::BidExceedsCeiling);
+    }
+
+    // Returning bidders may only raise their standing bid
+    if max_bid.max(bid_amount) <= standing_bid {
+        return Ok(BidCheck::IncreaseOnly);
+    }
+
+    // Bids must be strictly higher than the current price. Transactions in the same
+    // slot are executed one after another, so of two equal bids only the one that
+    // lands first is accepted and the other fails here.
+    if bid_amount <= auction.current_price {
+        return Ok(BidCheck::BidTooLow);
+    }
+
+    // Amounts below the auction's raw floor are almost always mis-scaled
+    if bid_amount < auction.min_bid_raw {
+        return Ok(BidCheck::BidBelowMinimumUnit);
+    }
+
+    // Once there is a bid, every raise must meet the configured increment; until
+    // then the opening bid floor applies
+    if auction.highest_bidder.is_some() {
+        let minimum_bid = auction
+            .current_price
+            .checked_add(auction.bid_increment()?)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        if bid_amount < minimum_bid {
+            return Ok(BidCheck::BidIncrementTooSmall);
+        }
+    } else if bid_amount < auction.opening_bid {
+        return Ok(BidCheck::OpeningBidTooLow);
+    }
+
+    Ok(BidCheck::Accepted)
+}
+
+/// Places an English bid of `bid_amount`, with an optional proxy ceiling of `max_bid`
+fn apply_bid<'info>(
+    mut accounts: BidAccounts<'_, 'info>,
+    bid_amount: u64,
+    max_bid: u64,
+    proof: Vec<[u8; 32]>,
+) -> Result<BidResult> {
+    let auction = accounts.auction;
+    let bid = accounts.bid;
+    let now = Clock::get()?.unix_timestamp;
+
+    // A record left by an earlier auction at this address holds nothing here
+    if auction.is_stale_bid(bid) {
+        bid.clear_stale();
+    }
+
+    check_bid(
+        accounts.auction_house,
+        auction,
+        &accounts.bidder,
+        bid.amount,
+        bid_amount,
+        max_bid,
+        &proof,
+        now,
+    )?
+    .require_accepted()?;
+
+    // Scheduled auctions open for bidding at start_time
+    auction.activate_if_started(now);
+
+    // Bidders cannot refer themselves into a share of the house fee
+    require!(
+        accounts.referrer != Some(accounts.bidder),
+        AuctionHouseError::InvalidReferrer
+    );
+    let ceiling = max_bid.max(bid_amount);
+
+    // Proxy ceiling of the current leader, when this bid challenges someone else
+    let bidder_key = accounts.bidder;
+    let leader = match auction.highest_bidder {
+        Some(leader) if leader != bidder_key => {
+            let leader_bid = accounts
+                .previous_bid
+                .as_deref()
+                .ok_or(AuctionHouseError::MissingPreviousBidder)?;
+            require!(
+                leader_bid.auction == auction.key() && leader_bid.bidder == leader,
+                AuctionHouseError::InvalidPreviousBidder
+            );
+            Some((leader, leader_bid.max_bid))
+        }
+        _ => None,
+    };
+
+    // The bond is posted with a bidder's first bid, whether or not it takes the lead
+    if auction.bid_bond > 0 && bid.bond == 0 {
+        let bond = auction.bid_bond;
+        match accounts.bidder_escrow.as_deref_mut() {
+            Some(bidder_escrow) => draw_from_bidder_escrow(
+                bidder_escrow,
+                accounts.bidder_escrow_token_account,
+                auction,
+                accounts.auction_token_account,
+                accounts.treasury_mint,
+                accounts.token_program,
+                bond,
+            )?,
+            None => deposit_to_escrow(
+                auction,
+                accounts
+                    .bidder_wallet
+                    .ok_or(AuctionHouseError::MissingPaymentAccount)?,
+                accounts.bidder_token_account,
+                accounts.auction_token_account,
+                accounts.treasury_mint,
+                accounts.token_program,
+                accounts.system_program,
+                bond,
+            )?,
+        }
+        bid.bond = bond;
+    }
+
+    // Sequence number of this bid within the auction, making the order in which
+    // bids landed auditable from bid records and events
+    let bid_nonce = auction
+        .bid_count
+        .checked_add(1)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+
+    match leader {
+        // A proxy ceiling equal to the challenger's keeps the lead: ties go to the
+        // bid placed first
+        Some((leader, leader_max_bid)) if leader_max_bid >= ceiling => {
+            // The standing proxy covers this bid, so the leader keeps the lead and
+            // rises only to one increment over the challenger, capped at its max.
+            // The challenger is outbid on the spot and nothing of theirs is escrowed.
+            auction.current_price = auction.next_bid_over(ceiling)?.min(leader_max_bid);
+            auction.second_highest_bid = auction.second_highest_bid.max(ceiling);
+            auction.record_bid(bidder_key, bid_amount, now);
+            let leader_price = auction.current_price;
+            auction.record_bid(leader, leader_price, now);
+            if let Some(notify_program) = &accounts.notify_program {
+                notify_outbid(notify_program, &auction.key(), &bidder_key, leader_price)?;
+            }
+
+            emit!(BidPlaced {
+                auction: auction.key(),
+                bidder: bidder_key,
+                amount: bid_amount,
+                bid_nonce,
+                timestamp: now,
+            });
+            emit!(BidPlaced {
+                auction: auction.key(),
+                bidder: leader,
+                amount: auction.current_price,
+                bid_nonce,
+                timestamp: now,
+            });
+        }
+        _ => {
+            // The bidder takes the lead, only at one increment over an outbid proxy
+            let price = match leader {
+                Some((_, leader_max_bid)) => auction
+                    .next_bid_over(leader_max_bid)?
+                    .min(ceiling)
+                    .max(bid_amount),
+                None => bid_amount,
+            };
+
+            // Escrow the full ceiling, topping up any amount already escrowed
+            let top_up = auction
+                .escrow_for(ceiling)?
+                .checked_sub(auction.escrow_for(bid.amount)?)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+            match accounts.bidder_escrow.as_deref_mut() {
+                // Prefunded bidders draw on their escrow balance instead of their wallet
+                Some(bidder_escrow) => draw_from_bidder_escrow(
+                    bidder_escrow,
+                    accounts.bidder_escrow_token_account,
+                    auction,
+                    accounts.auction_token_account,
+                    accounts.treasury_mint,
+                    accounts.token_program,
+                    top_up,
+                )?,
+                None => deposit_to_escrow(
+                    auction,
+                    accounts
+                        .bidder_wallet
+                        .ok_or(AuctionHouseError::MissingPaymentAccount)?,
+                    accounts.bidder_token_account,
+                    accounts.auction_token_account,
+                    accounts.treasury_mint,
+                    accounts.token_program,
+                    accounts.system_program,
+                    top_up,
+                )?,
+            }
+
+            // Refund the previous highest bidder now that they have been outbid
+            if auction.highest_bidder == Some(bidder_key) {
+                // Raising your own bid must not pass the same bid account twice
+                require!(
+                    accounts.previous_bid.is_none(),
+                    AuctionHouseError::InvalidPreviousBidder
+                );
+            } else {
+                let outbid = auction.highest_bidder;
+                refund_highest_bidder(
+                    auction,
+                    accounts.previous_bid.as_deref_mut(),
+                    accounts.previous_bidder_token_account,
+                    accounts.previous_bidder.clone(),
+                    accounts.auction_token_account,
+                    accounts.treasury_mint,
+                    accounts.token_program,
+                )?;
+                if let (Some(outbid), Some(notify_program)) = (outbid, &accounts.notify_program) {
+                    notify_outbid(notify_program, &auction.key(), &outbid, price)?;
+                }
+            }
+
+            // The outbid leader's ceiling is now the runner-up bid
+            if let Some((_, leader_max_bid)) = leader {
+                auction.second_highest_bid = leader_max_bid;
+            }
+            auction.current_price = price;
+            auction.highest_bidder = Some(bidder_key);
+            auction.referrer = accounts.referrer;
+            auction.winner_rebate_basis_points = accounts
+                .loyalty
+                .map_or(0, |loyalty| loyalty.rebate_basis_points);
+            bid.amount = ceiling;
+            bid.max_bid = ceiling;
+            auction.record_bid(bidder_key, price, now);
+
+            emit!(BidPlaced {
+                auction: auction.key(),
+                bidder: bidder_key,
+                amount: price,
+                bid_nonce,
+                timestamp: now,
+            });
+        }
+    }
+
+    // Update auction state
+    auction.bid_count = bid_nonce;
+    auction.last_bid_time = now;
+
+    // Late bids extend the auction so other bidders get a chance to respond
+    if auction.extension_window > 0 {
+        let extension_start = auction
+            .end_time
+            .checked_sub(auction.extension_window)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        if now > extension_start {
+            auction.end_time = auction.extended_end_time()?;
+        }
+    }
+
+    // Create or update bid record
+    bid.auction = auction.key();
+    bid.bidder = bidder_key;
+    bid.timestamp = now;
+    bid.bid_nonce = bid_nonce;
+
+    Ok(BidResult {
+        is_leading: auction.highest_bidder == Some(bidder_key),
+        new_current_price: auction.current_price,
+        min_next_bid: auction.minimum_next_bid()?,
+        end_time: auction.end_time,
+    })
+}
+
+/// Message a bidder signs to authorize a relayed bid: the auction key followed by
+/// the little-endian amount, nonce and expiry, then the referrer's key, or the
+/// default key when the bid has no referrer
+fn bid_signature_message(
+    auction: &Pubkey,
+    amount: u64,
+    nonce: u64,
+    expiry: i64,
+    referrer: Option<Pubkey>,
+) -> Vec<u8> {
+    [
+        auction.as_ref(),
+        &amount.to_le_bytes(),
+        &nonce.to_le_bytes(),
+        &expiry.to_le_bytes(),
+        referrer.unwrap_or_default().as_ref(),
+    ]
+    .concat()
+}
+
+/// Checks that the instruction before the current one is an Ed25519 program
+/// instruction verifying `signer`'s signature over exactly `message`. The Ed25519
+/// program fails the transaction on a bad signature, so only its inputs are checked.
+fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)?;
+    let index = current
+        .checked_sub(1)
+        .ok_or(AuctionHouseError::InvalidBidSignature)?;
+    let ed25519_ix = load_instruction_at_checked(index as usize, instructions)?;
+    require!(
+        ed25519_ix.program_id == ed25519_program::ID,
+        AuctionHouseError::InvalidBidSignature
+    );
+
+    // One signature, laid out as a u8 count, a padding byte and seven u16 offsets:
+    // signature, its instruction, public key, its instruction, message, message size
+    // and the message's instruction. u16::MAX points into the Ed25519 data itself.
+    let data = &ed25519_ix.data;
+    let offset = |at: usize| -> Result<usize> {
+        data.get(at..at + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(|| AuctionHouseError::InvalidBidSignature.into())
+    };
+    require!(
+        data.first() == Some(&1)
+            && offset(4)? == u16::MAX as usize
+            && offset(8)? == u16::MAX as usize
+            && offset(14)? == u16::MAX as usize,
+        AuctionHouseError::InvalidBidSignature
+    );
+
+    let public_key_offset = offset(6)?;
+    let message_offset = offset(10)?;
+    let message_size = offset(12)?;
+    require!(
+        data.get(public_key_offset..public_key_offset + 32) == Some(signer.as_ref())
+            && data.get(message_offset..message_offset + message_size) == Some(message),
+        AuctionHouseError::InvalidBidSignature
+    );
+
+    Ok(())
+}
+
+/// Accounts a new auction is validated against and escrows its NFT into, shared by
+/// create_auction and create_auction_with_ata
+struct ListingAccounts<'a, 'info> {
+    auction: &'a mut Account<'info, Auction>,
+    auction_house: &'a Account<'info, AuctionHouse>,
+    registered_mint: Option<&'a RegisteredTreasuryMint>,
+    token_mint: &'a InterfaceAccount<'info, Mint>,
+    metadata: &'a AccountInfo<'info>,
+    token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    auction_nft_account: &'a InterfaceAccount<'info, TokenAccount>,
+    seller_index: &'a mut Account<'info, SellerIndex>,
+    treasury_mint: &'a InterfaceAccount<'info, Mint>,
+    authority: &'a Signer<'info>,
+    auction_house_authority: Option<&'a Signer<'info>>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+}
+
+/// Validates a new auction, moves its NFT into `auction_nft_account` and
+/// initializes the auction account
+fn list_auction<'info>(
+    accounts: ListingAccounts<'_, 'info>,
+    auction_bump: u8,
+    args: CreateAuctionArgs,
+) -> Result<()> {
+    // A paused house takes no new listings, bids or purchases, but settlement
+    // and withdrawals keep working so users can exit
+    require!(
+        !accounts.auction_house.paused,
+        AuctionHouseError::AuctionHousePaused
+    );
+
+    // Curated houses must co-sign every listing
+    if accounts.auction_house.requires_sign_off {
+        let signed_off = accounts
+            .auction_house_authority
+            .is_some_and(|signer| signer.key() == accounts.auction_house.authority);
+        require!(signed_off, AuctionHouseError::AuctionHouseNotSignedOff);
+    }
+
+    // Only the house authority can waive fees, and only ever downwards
+    if let Some(fee_override) = args.fee_override {
+        let authorized = accounts
+            .auction_house_authority
+            .is_some_and(|signer| signer.key() == accounts.auction_house.authority);
+        require!(authorized, AuctionHouseError::Unauthorized);
+        require!(
+            fee_override <= accounts.auction_house.seller_fee_basis_points,
+            AuctionHouseError::FeeOverrideTooHigh
+        );
+    }
+
+    // Auctions are priced in the house's treasury mint or one it has registered
+    require!(
+        accounts.auction_house.accepts_treasury_mint(
+            &accounts.treasury_mint.key(),
+            accounts.registered_mint,
+        ),
+        AuctionHouseError::TreasuryMintNotRegistered
+    );
+
+    // Bidding must stay open for at least the house minimum, measured from when it
+    // opens, and close within the house maximum of now
+    let now = Clock::get()?.unix_timestamp;
+    let auction_house = accounts.auction_house;
+    let opens_at = args.start_time.max(now);
+    let earliest_end = opens_at
+        .checked_add(auction_house.min_auction_duration)
+        .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+    require!(
+        args.end_time > earliest_end,
+        AuctionHouseError::InvalidAuctionDuration
+    );
+    if auction_house.max_auction_duration > 0 {
+        let latest_end = now
+            .checked_add(auction_house.max_auction_duration)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?;
+        require!(
+            args.end_time < latest_end,
+            AuctionHouseError::InvalidAuctionDuration
+        );
+    }
+
+    let asset = read_metadata_asset(accounts.metadata, &accounts.token_mint.key())?;
+
+    // Curated houses only take NFTs verified as part of their collection
+    if let Some(allowed_collection) = accounts.auction_house.allowed_collection {
+        let in_collection = asset
+            .as_ref()
+            .and_then(|asset| asset.collection.as_ref())
+            .is_some_and(|collection| collection.verified && collection.key == allowed_collection);
+        require!(in_collection, AuctionHouseError::CollectionNotAllowed);
+    }
+    let programmable = asset.as_ref().is_some_and(|asset| asset.is_programmable());
+
+    // A delegate or freeze authority could move or lock the NFT out from under
+    // the auction
+    require!(
+        is_transferable(accounts.token_account, programmable),
+        AuctionHouseError::TokenAccountNotTransferable
+    );
+
+    // Every auction sells at least one token, and a zero minimum price is only
+    // allowed behind a reserve, so nothing is given away by accident
+    require!(
+        args.token_size > 0 && (args.minimum_price > 0 || args.reserve_price > 0),
+        AuctionHouseError::InvalidAuctionParameters
+    );
+
+    // Outside multi-unit mode the auction sells exactly one NFT
+    if !args.multi_unit {
+        let token_mint = accounts.token_mint;
+        require!(
+            token_mint.decimals == 0 && token_mint.supply == 1 && args.token_size == 1,
+            AuctionHouseError::NotAnNft
+        );
+    }
+
+    // Names and URIs are stored zero-padded in fixed-size fields
+    let name = padded_bytes::<MAX_AUCTION_NAME_LEN>(&args.name)?;
+    let uri = padded_bytes::<MAX_AUCTION_URI_LEN>(&args.uri)?;
+
+    // Extensions may only ever push end_time later
+    require!(
+        args.extension_window >= 0 && args.extension_amount >= 0,
+        AuctionHouseError::InvalidExtensionSettings
+    );
+    require!(
+        args.min_bid_interval >= 0,
+        AuctionHouseError::InvalidAuctionParameters
+    );
+    require!(
+        args.max_end_time == 0 || args.max_end_time >= args.end_time,
+        AuctionHouseError::InvalidExtensionSettings
+    );
+
+    // Dutch auctions need a declining price over a non-empty window
+    if args.auction_type == AuctionType::DutchDescending as u8 {
+        require!(
+            args.start_price >= args.end_price && args.start_time < args.end_time,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+    } else if args.auction_type == AuctionType::SealedSecondPrice as u8 {
+        // Sealed bids are revealed in a window that opens at end_time
+        require!(
+            args.reveal_window > 0,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+    } else if args.auction_type == AuctionType::PennyAuction as u8 {
+        // Each penny bid must move both the price and the clock
+        require!(
+            args.min_bid_increment > 0 && args.extension_amount > 0,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+    } else {
+        require!(
+            args.auction_type == AuctionType::EnglishAscending as u8,
+            AuctionHouseError::InvalidAuctionParameters
+        );
+    }
+
+    // Bonds are posted through place_bid, so only English auctions take them
+    require!(
+        (args.bid_bond == 0 || args.auction_type == AuctionType::EnglishAscending as u8)
+            && args.bond_claim_window >= 0,
+        AuctionHouseError::InvalidAuctionParameters
+    );
+
+    // Sealed auctions always clear at the second price; the choice is for English ones
+    require!(
+        args.pricing_rule == PricingRule::FirstPrice
+            || args.auction_type == AuctionType::EnglishAscending as u8,
+        AuctionHouseError::InvalidAuctionParameters
+    );
+
+    // Tiers must be sorted so each price falls in exactly one, and only English
+    // bids step by increments over the price
+    require!(
+        valid_increment_tiers(&args.increment_tiers)
+            && (args.increment_tiers.is_empty()
+                || args.auction_type == AuctionType::EnglishAscending as u8),
+        AuctionHouseError::InvalidIncrementTiers
+    );
+
+    // Per-unit pricing is only supported for English auctions
+    require!(
+        !args.multi_unit || args.auction_type == AuctionType::EnglishAscending as u8,
+        AuctionHouseError::InvalidAuctionParameters
+    );
+
+    // Move the NFT into the auction-owned escrow for the duration of the auction.
+    // Programmable NFTs can only move through the metadata program, which enforces
+    // their rule set.
+    if programmable {
+        transfer_programmable(
+            accounts.remaining_accounts,
+            &ProgrammableTransfer {
+                source: accounts.token_account.to_account_info(),
+                source_owner: accounts.authority.to_account_info(),
+                destination: accounts.auction_nft_account.to_account_info(),
+                destination_owner: accounts.auction.to_account_info(),
+                mint: accounts.token_mint.to_account_info(),
+                authority: accounts.authority.to_account_info(),
+                payer: accounts.authority.to_account_info(),
+                token_program: accounts.token_program.to_account_info(),
+            },
+            args.token_size,
+            &[],
+        )?;
+    } else {
+        let transfer_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.token_account.to_account_info(),
+                mint: accounts.token_mint.to_account_info(),
+                to: accounts.auction_nft_account.to_account_info(),
+                authority: accounts.authority.to_account_info(),
+            },
+        );
+
+        token_interface::transfer_checked(
+            transfer_ctx,
+            args.token_size,
+            accounts.token_mint.decimals,
+        )?;
+    }
+
+    let auction = accounts.auction;
+    auction.authority = accounts.authority.key();
+    auction.auction_house = accounts.auction_house.key();
+    auction.token_mint = accounts.token_mint.key();
+    auction.token_account = accounts.auction_nft_account.key();
+    auction.treasury_mint = accounts.treasury_mint.key();
+    auction.treasury_decimals = accounts.treasury_mint.decimals;
+    auction.auction_type = args.auction_type;
+    auction.pricing_rule = args.pricing_rule;
+    auction.token_size = args.token_size;
+    auction.multi_unit = args.multi_unit;
+    auction.minimum_price = args.minimum_price;
+    auction.opening_bid = args.opening_bid;
+    auction.min_bid_raw = args.min_bid_raw;
+    auction.buy_now_price = args.buy_now_price;
+    auction.reserve_price = args.reserve_price;
+    auction.start_price = args.start_price;
+    auction.end_price = args.end_price;
+    auction.created_at = now;
+    auction.start_time = args.start_time;
+    auction.end_time = args.end_time;
+    auction.current_price = args.minimum_price;
+    auction.min_bid_increment = args.min_bid_increment;
+    auction.min_bid_increment_bps = args.min_bid_increment_bps;
+    auction.increment_tiers = args.increment_tiers;
+    auction.bid_fee = if args.auction_type == AuctionType::PennyAuction as u8 {
+        args.bid_fee
+    } else {
+        0
+    };
+    auction.bid_bond = args.bid_bond;
+    auction.bond_claim_window = args.bond_claim_window;
+    auction.fee_override = args.fee_override;
+    auction.proceeds_destination = args
+        .proceeds_destination
+        .unwrap_or_else(|| accounts.authority.key());
+
+    // English and Dutch winners can escrow fees on top of their price. The rate is
+    // fixed now so escrowed bids keep covering it; sealed and penny auctions always
+    // charge the seller.
+    let charges_buyer = args.auction_type == AuctionType::EnglishAscending as u8
+        || args.auction_type == AuctionType::DutchDescending as u8;
+    auction.buyer_premium_basis_points =
+        if accounts.auction_house.fee_payer_model == FeePayerModel::BuyerPays && charges_buyer {
+            auction
+                .fee_basis_points(accounts.auction_house)
+                .checked_add(accounts.auction_house.protocol_fee_basis_points)
+                .ok_or(AuctionHouseError::ArithmeticOverflow)?
+        } else {
+            0
+        };
+    auction.extension_window = args.extension_window;
+    auction.extension_amount = args.extension_amount;
+    auction.max_end_time = args.max_end_time;
+    auction.min_bid_interval = args.min_bid_interval;
+    auction.reveal_end_time = if args.auction_type == AuctionType::SealedSecondPrice as u8 {
+        args.end_time
+            .checked_add(args.reveal_window)
+            .ok_or(AuctionHouseError::ArithmeticOverflow)?
+    } else {
+        0
+    };
+    auction.second_highest_bid = 0;
+    auction.highest_bidder = None;
+    auction.referrer = None;
+    auction.winner_rebate_basis_points = 0;
+    auction.settled = false;
+    auction.settled_by = None;
+    auction.claimed = false;
+    auction.escrowed_total = 0;
+    auction.withdrawn_total = 0;
+    auction.settlement_verified = false;
+    auction.resolution = None;
+    auction.programmable = programmable;
+    auction.bid_count = 0;
+    auction.last_bid_time = 0;
+    auction.allowlist_root = args.allowlist_root;
+    auction.name = name;
+    auction.uri = uri;
+    auction.status = if args.start_time > now {
+        AuctionStatus::Pending
+    } else {
+        AuctionStatus::Active
+    };
+    auction.frozen = false;
+    auction.bump = auction_bump;
+    auction.original_authority = accounts.authority.key();
+    auction.version = AUCTION_VERSION;
+    auction.clear_bid_history();
+
+    // Sellers at the house's limit must let an auction finish before listing more
+    accounts
+        .seller_index
+        .open_slot(auction, accounts.auction_house.max_active_auctions_per_seller)?;
+    accounts
+        .seller_index
+        .record(accounts.authority.key(), auction.key())?;
+
+    emit!(AuctionCreated {
+        auction: auction.key(),
+        seller: auction.authority,
+        minimum_price: args.minimum_price,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+/// Accounts a settlement returns the NFT through and pays the sale out of, shared
+/// by end_auction and batch_end_auctions
+struct SettlementAccounts<'a, 'info> {
+    auction_house: &'a Account<'info, AuctionHouse>,
+    auction_nft_account: &'a InterfaceAccount<'info, TokenAccount>,
+    owner_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    auction_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    auction_house_treasury: &'a InterfaceAccount<'info, TokenAccount>,
+    protocol_fee_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    seller_proceeds_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    seller: Option<AccountInfo<'info>>,
+    referrer_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    referrer: Option<AccountInfo<'info>>,
+    winner_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    winner: Option<AccountInfo<'info>>,
+    metadata: &'a AccountInfo<'info>,
+    creator_accounts: &'info [AccountInfo<'info>],
+    token_mint: &'a InterfaceAccount<'info, Mint>,
+    treasury_mint: &'a InterfaceAccount<'info, Mint>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    treasury_token_program: &'a Interface<'info, TokenInterface>,
+}
+
+/// Settles an auction whose bidding has closed: the NFT goes back to the seller if
+/// it did not sell, otherwise the sale price is paid out and the winner can claim
+fn settle_auction<'info>(
+    auction: &mut Account<'info, Auction>,
+    accounts: &SettlementAccounts<'_, 'info>,
+    settler: Pubkey,
+    now: i64,
+) -> Result<()> {
+    let auction_house = accounts.auction_house;
+
+    // Settlement is permissionless unless the house requires its sign-off
+    if auction_house.requires_sign_off {
+        require!(
+            settler == auction_house.authority,
+            AuctionHouseError::AuctionHouseNotSignedOff
+        );
+    }
+
+    // Settlement runs once, regardless of what the status field says
+    require!(!auction.settled, AuctionHouseError::AuctionAlreadySettled);
+
+    // A frozen auction is in dispute until resolve_disputed_auction settles it
+    require!(!auction.frozen, AuctionHouseError::AuctionFrozen);
+
+    // Check if auction has ended; sealed auctions also wait out their reveal window
+    require!(
+        now >= auction.closes_at(),
+        AuctionHouseError::AuctionNotEnded
+    );
+    require!(
+        now >= auction.settles_at(auction_house.settlement_delay)?,
+        AuctionHouseError::SettlementWindowNotElapsed
+    );
+
+    // Scheduled auctions count as open once start_time has passed
+    auction.activate_if_started(now);
+
+    // Check if auction is still active
+    require!(
+        auction.is_active(),
+        AuctionHouseError::AuctionNotActive
+    );
+
+    // The sealed winner pays the second-highest price and withdraws the rest of
+    // their collateral through withdraw_bid
+    if auction.auction_type == AuctionType::SealedSecondPrice as u8 {
+        auction.current_price = auction.sealed_clearing_price();
+    } else if auction.pricing_rule == PricingRule::SecondPrice {
+        // Likewise the English winner withdraws whatever they escrowed above the
+        // runner-up price
+        auction.current_price = auction.second_price_clearing_price()?;
+    }
+
+    let reserve_met = auction.current_price >= auction.reserve_price;
+
+    // Update auction status
+    auction.status = if auction.highest_bidder.is_some() && !reserve_met {
+        AuctionStatus::ReserveNotMet
+    } else {
+        AuctionStatus::Ended
+    };
+    auction.settled = true;
+    auction.settled_by = Some(settler);
+
+    // Every copy on sale must still be in escrow, whoever ends up receiving them
+    require!(
+        accounts.auction_nft_account.amount >= auction.token_size,
+        AuctionHouseError::InsufficientEscrow
+    );
+
+    // The winner pulls the NFT with claim_nft; without a sale it goes straight
+    // back to the seller, whether there were no bids or the reserve was not met.
+    // Programmable NFTs and bundles need accounts settlement does not take, so
+    // their seller reclaims them with claim_nft instead.
+    if (auction.highest_bidder.is_none() || !reserve_met) && !auction.held_until_claimed() {
+        transfer_from_auction(
+            auction,
+            accounts.auction_nft_account,
+            accounts.owner_token_account,
+            accounts.token_mint,
+            accounts.token_program,
+            auction.token_size,
+        )?;
+    }
+
+    // Pay the seller as soon as the sale is final; multi-unit winners pay the
+    // per-unit price for every copy
+    if auction.highest_bidder.is_some() && reserve_met {
+        // Under BuyerPays the escrow must also cover the buyer premium
+        let total = auction.total_for(auction.current_price)?;
+        if let Some(escrow) = accounts.auction_token_account {
+            require!(
+                escrow.amount >= auction.escrow_for(auction.current_price)?,
+                AuctionHouseError::InsufficientEscrow
+            );
+        }
+        pay_out_sale(
+            auction,
+            auction_house,
+            accounts.auction_token_account,
+            accounts.auction_house_treasury,
+            accounts.protocol_fee_account,
+            accounts.seller_proceeds_account,
+            accounts.seller.clone(),
+            accounts.referrer_token_account,
+            accounts.referrer.clone(),
+            accounts.winner_token_account,
+            accounts.winner.clone(),
+            accounts.metadata,
+            accounts.creator_accounts,
+            accounts.treasury_mint,
+            accounts.treasury_token_program,
+            total,
+        )?;
+    }
+
+    emit!(AuctionEnded {
+        auction: auction.key(),
+        winner: auction.highest_bidder.filter(|_| reserve_met),
+        settler,
+        price: auction.current_price,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+/// Emitted when a proposed auction house authority accepts the transfer
+#[event]
+pub struct AuthorityTransferred {
+    pub auction_house: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when a listing is created and its NFT escrowed
+#[event]
+pub struct AuctionCreated {
+    pub auction: Pubkey,
+    pub seller: Pubkey,
+    pub minimum_price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted for every accepted bid; amount is the bidder's new total bid
+#[event]
+pub struct BidPlaced {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    /// Position of the bid in the auction's bid sequence
+    pub bid_nonce: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a sole bidder retracts through cancel_bid; amount is the refunded escrow
+#[event]
+pub struct BidCancelled {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted whenever escrowed bid funds are returned to a bidder, whether outbid or
+/// withdrawing after the auction
+#[event]
+pub struct BidRefunded {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted for every fee taken from a sale or bid; sale is the auction, listing or
+/// offer it was charged on
+#[event]
+pub struct FeePaid {
+    pub sale: Pubkey,
+    pub kind: FeeKind,
+    /// Treasury or fee account credited, or the wallet for referrals and rebates
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted for each creator's share of an auction sale's royalties
+#[event]
+pub struct RoyaltyPaid {
+    pub auction: Pubkey,
+    pub creator: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted when fees leave a treasury or the fee account, by withdrawal or by sweep
+/// at settlement
+#[event]
+pub struct TreasuryWithdrawn {
+    pub auction_house: Pubkey,
+    pub treasury: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when reclaim_bond pays an unclaiming winner's bid bond to the seller
+#[event]
+pub struct BondForfeited {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a sealed bid is committed; collateral is public, the bid amount is not
+#[event]
+pub struct BidCommitted {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub collateral: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a sealed bid is revealed
+#[event]
+pub struct BidRevealed {
+    pub auction: Pubkey,
+    pub bidder: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted on settlement; winner is None when there were no bids or the reserve was not met
+#[event]
+pub struct AuctionEnded {
+    pub auction: Pubkey,
+    pub winner: Option<Pubkey>,
+    pub settler: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the seller pushes back an open auction's end time
+#[event]
+pub struct AuctionExtended {
+    pub auction: Pubkey,
+    pub old_end_time: i64,
+    pub new_end_time: i64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the house authority recovers a stranded NFT with emergency_withdraw
+#[event]
+pub struct EmergencyWithdrawal {
+    pub auction: Pubkey,
+    pub recipient: Pubkey,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when a seller hands an auction over with transfer_auction_authority
+#[event]
+pub struct AuctionAuthorityTransferred {
+    pub auction: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when sweep_closed returns the rent of closed accounts to their payer
+#[event]
+pub struct RentReclaimed {
+    pub payer: Pubkey,
+    pub accounts_closed: u32,
+    pub lamports: u64,
+}
+
+/// Emitted when migrate_auction brings an auction up to the current layout
+#[event]
+pub struct AuctionMigrated {
+    pub auction: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+/// Emitted when migrate_auction_house brings a house up to the current layout
+#[event]
+pub struct AuctionHouseMigrated {
+    pub auction_house: Pubkey,
+}
+
+/// Emitted when the house authority resolves a disputed auction
+#[event]
+pub struct AuctionResolved {
+    pub auction: Pubkey,
+    pub resolution: Resolution,
+    pub authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when the seller cancels and the NFT is returned
+#[event]
+pub struct AuctionCancelled {
+    pub auction: Pubkey,
+    pub seller: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted instead of AuctionEnded when an auction settles through buy_now
+#[event]
+pub struct BuyNowExecuted {
+    pub auction: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when an NFT is listed at a fixed price
+#[event]
+pub struct ListingCreated {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when a buyer pays the listing price and receives the NFT
+#[event]
+pub struct ListingSold {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub buyer: Pubkey,
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the seller cancels a listing and the NFT is returned
+#[event]
+pub struct ListingCancelled {
+    pub listing: Pubkey,
+    pub seller: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Emitted when an offer is escrowed against an NFT
+#[event]
+pub struct OfferMade {
+    pub offer: Pubkey,
+    pub offerer: Pubkey,
+    pub owner: Pubkey,
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub offer_expiry: i64,
+}
+
+/// Emitted when the owner accepts an offer and the NFT changes hands
+#[event]
+pub struct OfferAccepted {
+    pub offer: Pubkey,
+    pub offerer: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+/// Emitted when the offerer cancels and the escrow is refunded
+#[event]
+pub struct OfferCancelled {
+    pub offer: Pubkey,
+    pub offerer: Pubkey,
+    pub timestamp: i64,
+}
+
+#[error_code]
+pub enum AuctionHouseError {
+    #[msg("Auction is not active")]
+    AuctionNotActive,
+    #[msg("Auction has ended")]
+    AuctionEnded,
+    #[msg("Auction has not ended yet")]
+    AuctionNotEnded,
+    #[msg("Bid amount is too low")]
+    BidTooLow,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Previous highest bidder accounts are required")]
+    MissingPreviousBidder,
+    #[msg("Previous highest bidder accounts do not match the auction")]
+    InvalidPreviousBidder,
+    #[msg("Auction is still active")]
+    AuctionStillActive,
+    #[msg("The winning bid cannot be withdrawn")]
+    WinningBidNotWithdrawable,
+    #[msg("New bid must be greater than your existing bid")]
+    IncreaseOnly,
+    #[msg("Bid does not meet the minimum bid increment")]
+    BidIncrementTooSmall,
+    #[msg("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[msg("Extension window and amount must not be negative, nor max_end_time before end_time")]
+    InvalidExtensionSettings,
+    #[msg("Buy now is not available for this auction")]
+    BuyNowNotAvailable,
+    #[msg("Auction house authority has not signed off")]
+    AuctionHouseNotSignedOff,
+    #[msg("Auction house does not allow changing the sale price")]
+    CannotChangeSalePrice,
+    #[msg("Auction already has bids")]
+    AuctionHasBids,
+    #[msg("Amount exceeds the available balance")]
+    InsufficientFunds,
+    #[msg("A payment account required by the auction's treasury mint is missing")]
+    MissingPaymentAccount,
+    #[msg("Invalid auction parameters")]
+    InvalidAuctionParameters,
+    #[msg("Instruction is not supported for this auction type")]
+    WrongAuctionType,
+    #[msg("Auction has not started yet")]
+    AuctionNotStarted,
+    #[msg("Auction authority cannot bid on their own auction")]
+    SelfBiddingNotAllowed,
+    #[msg("A sealed bid has already been committed for this bidder")]
+    BidAlreadyCommitted,
+    #[msg("Sealed bid has already been revealed")]
+    BidAlreadyRevealed,
+    #[msg("Revealed amount and nonce do not match the commitment")]
+    CommitmentMismatch,
+    #[msg("Reveal window has closed")]
+    RevealWindowClosed,
+    #[msg("NFT has already been claimed")]
+    NftAlreadyClaimed,
+    #[msg("Metadata account could not be parsed for this mint")]
+    InvalidMetadata,
+    #[msg("Creator payment account does not match the metadata creators")]
+    InvalidCreatorAccount,
+    #[msg("Auction house is paused")]
+    AuctionHousePaused,
+    #[msg("House and protocol fee basis points together cannot exceed 10000")]
+    InvalidFeeBasisPoints,
+    #[msg("Bidder is not on the auction allowlist")]
+    BidderNotAllowlisted,
+    #[msg("Auction has already been settled")]
+    AuctionAlreadySettled,
+    #[msg("Batch settlement accounts are missing or do not match their auction")]
+    InvalidBatchAccounts,
+    #[msg("Auction duration is outside the auction house bounds")]
+    InvalidAuctionDuration,
+    #[msg("Max bid must be zero or at least the bid amount")]
+    InvalidMaxBid,
+    #[msg("Settlement window has not elapsed")]
+    SettlementWindowNotElapsed,
+    #[msg("Settlement delay cannot be negative")]
+    InvalidSettlementDelay,
+    #[msg("Bid retraction is disabled or others have already bid")]
+    BidRetractionNotAllowed,
+    #[msg("Caller is not the highest bidder")]
+    NotHighestBidder,
+    #[msg("Escrow does not hold enough to settle the sale")]
+    InsufficientEscrow,
+    #[msg("Offer has expired")]
+    OfferExpired,
+    #[msg("Referrer account does not match the auction referrer")]
+    InvalidReferrer,
+    #[msg("NFT is not a verified member of the allowed collection")]
+    CollectionNotAllowed,
+    #[msg("Token mint is not an NFT: it must have 0 decimals and a supply of 1")]
+    NotAnNft,
+    #[msg("Programmable NFT transfer accounts are missing or invalid")]
+    MissingProgrammableAccounts,
+    #[msg("Bid placed too soon after the previous bid")]
+    BiddingTooFast,
+    #[msg("Winner account does not match the auction's highest bidder")]
+    InvalidWinnerAccount,
+    #[msg("Rebate basis points cannot exceed 10000")]
+    InvalidRebateBasisPoints,
+    #[msg("New end time must be later than the current end time")]
+    EndTimeNotExtended,
+    #[msg("Bundle accounts are missing or invalid")]
+    InvalidBundleAccounts,
+    #[msg("Programmable NFTs cannot be sold in a bundle")]
+    ProgrammableBundle,
+    #[msg("Emergency withdrawal timelock has not elapsed")]
+    EmergencyTimelockActive,
+    #[msg("Bidder funds are still held in the auction escrow")]
+    BidderFundsRemaining,
+    #[msg("Auction name or URI is too long")]
+    AuctionMetadataTooLong,
+    #[msg("Auction is frozen by the auction house")]
+    AuctionFrozen,
+    #[msg("The NFT is no longer held in the auction escrow")]
+    NftNotInEscrow,
+    #[msg("Treasury mint is not accepted by this auction house")]
+    TreasuryMintNotRegistered,
+    #[msg("The house's own treasury mint cannot be registered")]
+    InvalidTreasuryMint,
+    #[msg("Price is higher than the buyer's maximum")]
+    PriceExceededMax,
+    #[msg("Escrow still holds funds owed to bidders or the seller")]
+    SettlementIncomplete,
+    #[msg("Auction house does not allow cancelling an auction with bids")]
+    CannotCancelWithBids,
+    #[msg("NFT token account is frozen or has a delegate")]
+    TokenAccountNotTransferable,
+    #[msg("Winner's bid bond stays in escrow until the NFT is claimed")]
+    BidBondLocked,
+    #[msg("Bid bond cannot be forfeited")]
+    BondNotForfeitable,
+    #[msg("First bid is below the auction's opening bid")]
+    OpeningBidTooLow,
+    #[msg("Fee override cannot exceed the house fee")]
+    FeeOverrideTooHigh,
+    #[msg("Auction is not frozen for a dispute")]
+    AuctionNotDisputed,
+    #[msg("Seller already has the most active auctions the house allows")]
+    TooManyActiveAuctions,
+    #[msg("Seller index account is required")]
+    MissingSellerIndex,
+    #[msg("Increment tiers must be ascending by threshold with non-zero increments")]
+    InvalidIncrementTiers,
+    #[msg("Signed bid has expired")]
+    BidSignatureExpired,
+    #[msg("Signed bid nonce has already been used or is out of order")]
+    InvalidBidNonce,
+    #[msg("Signed bid is missing a matching Ed25519 signature verification")]
+    InvalidBidSignature,
+    #[msg("Bid is below the auction's minimum raw amount")]
+    BidBelowMinimumUnit,
+    #[msg("Account is not an auction of this program")]
+    InvalidAuctionAccount,
+    #[msg("Auction already uses the current layout")]
+    AuctionAlreadyMigrated,
+    #[msg("Creation cancel window cannot be negative")]
+    InvalidCancelWindow,
+    #[msg("Token account is not in the auction's treasury mint")]
+    MintMismatch,
+    #[msg("Account is not final or still holds escrow")]
+    AccountNotCloseable,
+    #[msg("Bid exceeds the auction house's maximum bid")]
+    BidExceedsCeiling,
+    #[msg("New end time cannot be later than the auction's max_end_time")]
+    EndTimePastMax,
+    #[msg("Auction house account is required")]
+    MissingAuctionHouse,
+    #[msg("Account is not an auction house of this program")]
+    InvalidAuctionHouseAccount,
+    #[msg("Auction house already uses the current layout")]
+    AuctionHouseAlreadyMigrated,
 }
 
-#[account]
-pub struct Bid {
-    pub auction: Pubkey,
-    pub bidder: Pubkey,
-    pub amount: u64,
-    pub timestamp: i64,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::bpf_loader;
+    use anchor_lang::solana_program::entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE};
+    use anchor_lang::solana_program::program_option::COption;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+    use anchor_lang::solana_program::program_utils::limited_deserialize;
+    use anchor_lang::solana_program::system_instruction::SystemInstruction;
+    use anchor_lang::InstructionData;
+    use anchor_spl::token::spl_token;
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::sync::Once;
+
+    /// An Auction with every field zeroed, as a freshly allocated account reads
+    fn zeroed_auction() -> Auction {
+        Auction::deserialize(&mut &vec![0u8; Auction::LEN][..]).unwrap()
+    }
+
+    fn overflow() -> anchor_lang::error::Error {
+        AuctionHouseError::ArithmeticOverflow.into()
+    }
+
+    #[test]
+    fn next_bid_over_errors_instead_of_wrapping() {
+        let mut auction = zeroed_auction();
+        auction.min_bid_increment = 1;
+
+        assert_eq!(auction.next_bid_over(u64::MAX - 1).unwrap(), u64::MAX);
+        assert_eq!(auction.next_bid_over(u64::MAX).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn percentage_increment_past_u64_errors() {
+        let mut auction = zeroed_auction();
+        auction.min_bid_increment_bps = 10_000;
+
+        assert_eq!(auction.increment_over(u64::MAX).unwrap(), u64::MAX);
+        auction.min_bid_increment_bps = 10_001;
+        assert_eq!(auction.increment_over(u64::MAX).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn minimum_next_bid_at_max_price_errors() {
+        let mut auction = zeroed_auction();
+        auction.current_price = u64::MAX;
+
+        assert_eq!(auction.minimum_next_bid().unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn multi_unit_total_past_u64_errors() {
+        let mut auction = zeroed_auction();
+        auction.multi_unit = true;
+        auction.token_size = 2;
+
+        assert_eq!(auction.total_for(u64::MAX / 2).unwrap(), u64::MAX - 1);
+        assert_eq!(auction.total_for(u64::MAX / 2 + 1).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn basis_points_of_max_amount() {
+        assert_eq!(basis_points_of(u64::MAX, 10_000).unwrap(), u64::MAX);
+        assert_eq!(basis_points_of(u64::MAX, 5_000).unwrap(), u64::MAX / 2);
+        assert_eq!(basis_points_of(u64::MAX, 10_001).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn raise_past_u64_errors_instead_of_being_accepted() {
+        let (auction_house, mut auction) = bidding_fixture();
+        auction.highest_bidder = Some(Pubkey::new_unique());
+        auction.current_price = u64::MAX - 1;
+        auction.min_bid_increment = 10;
+        let bidder = Pubkey::new_unique();
+
+        let result = check_bid(&auction_house, &auction, &bidder, 0, u64::MAX, 0, &[], 150);
+        assert_eq!(result.err().unwrap(), overflow());
+    }
+
+    #[test]
+    fn split_sale_of_max_price_conserves_it() {
+        let mut auction_house = zeroed_auction_house(RoundingPolicy::RemainderToSeller);
+        auction_house.protocol_fee_basis_points = 100;
+
+        let (fee, protocol_fee, royalties, proceeds) =
+            auction_house.split_sale(u64::MAX, 250, 500).unwrap();
+        let sum = fee as u128 + protocol_fee as u128 + royalties as u128 + proceeds as u128;
+        assert_eq!(sum, u64::MAX as u128);
+    }
+
+    /// Leaf and parent hashing as is_allowlisted does it
+    fn allowlist_leaf(wallet: &Pubkey) -> [u8; 32] {
+        hashv(&[wallet.as_ref()]).to_bytes()
+    }
+
+    fn allowlist_parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        hashv(&[&low, &high]).to_bytes()
+    }
+
+    #[test]
+    fn zero_allowlist_root_admits_everyone() {
+        let auction = zeroed_auction();
+
+        assert!(auction.is_allowlisted(&Pubkey::new_unique(), &[]));
+    }
+
+    #[test]
+    fn allowlisted_bidder_passes_and_others_fail() {
+        let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = wallets.iter().map(allowlist_leaf).collect();
+        let mut auction = zeroed_auction();
+        auction.allowlist_root = allowlist_parent(leaves[0], leaves[1]);
+
+        assert!(auction.is_allowlisted(&wallets[0], &[leaves[1]]));
+        assert!(auction.is_allowlisted(&wallets[1], &[leaves[0]]));
+        assert!(auction.admission(&wallets[0], &[leaves[1]]) == BidCheck::Accepted);
+
+        // Neither a wallet outside the tree nor a listed one with a bad proof gets in
+        assert!(!auction.is_allowlisted(&wallets[2], &[leaves[1]]));
+        assert!(!auction.is_allowlisted(&wallets[0], &[leaves[2]]));
+        assert!(!auction.is_allowlisted(&wallets[0], &[]));
+        assert!(auction.admission(&wallets[2], &[leaves[1]]) == BidCheck::BidderNotAllowlisted);
+    }
+
+    /// An English auction scheduled to run from 100 to 200
+    fn scheduled_auction(status: AuctionStatus) -> Auction {
+        let mut auction = zeroed_auction();
+        auction.start_time = 100;
+        auction.end_time = 200;
+        auction.status = status;
+        auction
+    }
+
+    #[test]
+    fn pending_auction_opens_at_start_time() {
+        let mut auction = scheduled_auction(AuctionStatus::Pending);
+
+        assert!(!auction.is_active() && !auction.is_final());
+        assert!(auction.is_open_or_pending());
+        assert!(!auction.can_bid(99));
+        assert!(auction.can_bid(100));
+        assert!(auction.effective_status(99) == AuctionStatus::Pending);
+        assert!(auction.effective_status(100) == AuctionStatus::Active);
+
+        auction.activate_if_started(99);
+        assert!(auction.status == AuctionStatus::Pending);
+        auction.activate_if_started(100);
+        assert!(auction.status == AuctionStatus::Active);
+    }
+
+    #[test]
+    fn active_auction_takes_bids_until_end_time() {
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+
+        assert!(auction.is_active() && !auction.is_final());
+        assert!(auction.is_open_or_pending());
+        assert!(auction.can_bid(199));
+        assert!(!auction.can_bid(200));
+        assert!(auction.effective_status(199) == AuctionStatus::Active);
+
+        // Past end_time it reports how it would settle
+        assert!(auction.effective_status(200) == AuctionStatus::Ended);
+        auction.highest_bidder = Some(Pubkey::new_unique());
+        auction.current_price = 10;
+        auction.reserve_price = 20;
+        assert!(auction.effective_status(200) == AuctionStatus::ReserveNotMet);
+    }
+
+    #[test]
+    fn final_auctions_take_no_bids() {
+        for status in [
+            AuctionStatus::Ended,
+            AuctionStatus::Cancelled,
+            AuctionStatus::ReserveNotMet,
+        ] {
+            let mut auction = scheduled_auction(status);
+
+            assert!(auction.is_final() && !auction.is_active());
+            assert!(!auction.is_open_or_pending());
+            assert!(!auction.can_bid(150));
+            assert!(auction.effective_status(150) == status);
+
+            auction.activate_if_started(150);
+            assert!(auction.status == status);
+        }
+    }
+
+    #[test]
+    fn sole_leader_may_retract() {
+        let leader = Pubkey::new_unique();
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.minimum_price = 100;
+        auction.current_price = 150;
+        auction.highest_bidder = Some(leader);
+        auction.bid_count = 1;
+
+        auction.check_retraction(&leader, 150).unwrap();
+        assert_eq!(
+            auction.check_retraction(&leader, 200).unwrap_err(),
+            AuctionHouseError::AuctionEnded.into()
+        );
+
+        auction.reopen_after_retraction();
+        assert!(auction.highest_bidder.is_none());
+        assert_eq!(auction.current_price, 100);
+        assert_eq!(auction.bid_count, 1);
+    }
+
+    #[test]
+    fn outbid_bids_cannot_be_retracted() {
+        let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.highest_bidder = Some(second);
+        auction.bid_count = 2;
+
+        assert_eq!(
+            auction.check_retraction(&first, 150).unwrap_err(),
+            AuctionHouseError::NotHighestBidder.into()
+        );
+        assert_eq!(
+            auction.check_retraction(&second, 150).unwrap_err(),
+            AuctionHouseError::BidRetractionNotAllowed.into()
+        );
+    }
+
+    #[test]
+    fn extension_moves_end_time_later() {
+        let auction = scheduled_auction(AuctionStatus::Active);
+
+        auction.check_extension(201, 0).unwrap();
+        auction.check_extension(i64::MAX, 0).unwrap();
+    }
+
+    #[test]
+    fn extension_cannot_shorten_the_auction() {
+        let auction = scheduled_auction(AuctionStatus::Active);
+
+        for new_end_time in [199, 200] {
+            assert_eq!(
+                auction.check_extension(new_end_time, 0).unwrap_err(),
+                AuctionHouseError::EndTimeNotExtended.into()
+            );
+        }
+    }
+
+    #[test]
+    fn extension_stays_within_the_house_maximum() {
+        // The maximum counts from created_at, not from the original end_time
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.created_at = 50;
+
+        auction.check_extension(299, 250).unwrap();
+        assert_eq!(
+            auction.check_extension(300, 250).unwrap_err(),
+            AuctionHouseError::InvalidAuctionDuration.into()
+        );
+    }
+
+    #[test]
+    fn extensions_accumulate_up_to_max_end_time() {
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.extension_amount = 25;
+        auction.max_end_time = 260;
+
+        let mut end_times = Vec::new();
+        for _ in 0..4 {
+            auction.end_time = auction.extended_end_time().unwrap();
+            end_times.push(auction.end_time);
+        }
+        assert_eq!(end_times, vec![225, 250, 260, 260]);
+    }
+
+    #[test]
+    fn extension_cannot_pass_max_end_time() {
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.max_end_time = 260;
+
+        auction.check_extension(260, 0).unwrap();
+        assert_eq!(
+            auction.check_extension(261, 0).unwrap_err(),
+            AuctionHouseError::EndTimePastMax.into()
+        );
+
+        // Once the seller has reached the cap neither they nor bids move it further
+        auction.end_time = 260;
+        auction.extension_amount = 25;
+        assert_eq!(
+            auction.check_extension(261, 0).unwrap_err(),
+            AuctionHouseError::EndTimePastMax.into()
+        );
+        assert_eq!(auction.extended_end_time().unwrap(), 260);
+    }
+
+    /// An open house and an English auction at 100 taking bids from 100 to 200
+    fn bidding_fixture() -> (AuctionHouse, Auction) {
+        let mut auction_house = zeroed_auction_house(RoundingPolicy::RemainderToSeller);
+        auction_house.absolute_max_bid = u64::MAX;
+        let mut auction = scheduled_auction(AuctionStatus::Active);
+        auction.authority = Pubkey::new_unique();
+        auction.minimum_price = 100;
+        auction.current_price = 100;
+        (auction_house, auction)
+    }
+
+    #[test]
+    fn seller_cannot_bid_on_own_auction() {
+        let (mut auction_house, auction) = bidding_fixture();
+        let seller = auction.authority;
+
+        let check = check_bid(&auction_house, &auction, &seller, 0, 150, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::SelfBiddingNotAllowed);
+
+        auction_house.allow_self_bid = true;
+        let check = check_bid(&auction_house, &auction, &seller, 0, 150, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::Accepted);
+    }
+
+    #[test]
+    fn bid_over_the_current_price_is_accepted() {
+        let (auction_house, auction) = bidding_fixture();
+        let bidder = Pubkey::new_unique();
+
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 150, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::Accepted);
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 100, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::BidTooLow);
+    }
+
+    #[test]
+    fn bid_past_the_ceiling_is_rejected() {
+        let (mut auction_house, auction) = bidding_fixture();
+        auction_house.absolute_max_bid = 1_000;
+        let bidder = Pubkey::new_unique();
+
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 1_001, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::BidExceedsCeiling);
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 1_000, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::Accepted);
+        // A proxy ceiling is held to it as well
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 150, 1_001, &[], 150).unwrap();
+        assert!(check == BidCheck::BidExceedsCeiling);
+    }
+
+    #[test]
+    fn bid_below_one_display_unit_is_rejected() {
+        let (auction_house, mut auction) = bidding_fixture();
+        auction.min_bid_raw = 1_000;
+        let bidder = Pubkey::new_unique();
+
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 999, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::BidBelowMinimumUnit);
+        let check = check_bid(&auction_house, &auction, &bidder, 0, 1_000, 0, &[], 150).unwrap();
+        assert!(check == BidCheck::Accepted);
+    }
+
+    /// Opening at 100 behind a reserve of 500, raised by at least 10 per bid
+    fn reserve_auction() -> Auction {
+        let mut auction = zeroed_auction();
+        auction.minimum_price = 100;
+        auction.current_price = 100;
+        auction.reserve_price = 500;
+        auction.min_bid_increment = 10;
+        auction
+    }
+
+    #[test]
+    fn minimum_winning_bid_without_bids() {
+        let mut auction = reserve_auction();
+        assert_eq!(auction.minimum_winning_bid().unwrap(), 500);
+
+        // Without a reserve the first bid only has to beat the opening price
+        auction.reserve_price = 0;
+        assert_eq!(auction.minimum_winning_bid().unwrap(), 101);
+    }
+
+    #[test]
+    fn minimum_winning_bid_below_reserve() {
+        // Once there is a bid, raising it is enough even while under the reserve
+        let mut auction = reserve_auction();
+        auction.highest_bidder = Some(Pubkey::new_unique());
+        auction.current_price = 200;
+
+        assert_eq!(auction.minimum_winning_bid().unwrap(), 210);
+    }
+
+    #[test]
+    fn minimum_winning_bid_above_reserve() {
+        let mut auction = reserve_auction();
+        auction.highest_bidder = Some(Pubkey::new_unique());
+        auction.current_price = 600;
+
+        assert_eq!(auction.minimum_winning_bid().unwrap(), 610);
+
+        // A percentage increment takes over once it outgrows the fixed one
+        auction.min_bid_increment_bps = 500;
+        assert_eq!(auction.minimum_winning_bid().unwrap(), 630);
+    }
+
+    /// xorshift64, so the conservation properties are checked over the same inputs
+    /// on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Prices of every magnitude, from a few units up to u64::MAX
+        fn price(&mut self) -> u64 {
+            self.next() >> (self.next() % 64)
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+    }
+
+    fn zeroed_auction_house(rounding_policy: RoundingPolicy) -> AuctionHouse {
+        let mut auction_house =
+            AuctionHouse::deserialize(&mut &vec![0u8; AuctionHouse::LEN][..]).unwrap();
+        auction_house.rounding_policy = rounding_policy;
+        auction_house
+    }
+
+    #[test]
+    fn largest_remainder_parts_add_up_to_gross() {
+        let mut rng = Rng(0x5eed_1e55_ca5c_ade5);
+        for _ in 0..10_000 {
+            let gross = rng.price();
+            let count = 1 + rng.below(6) as usize;
+            let weights: Vec<u64> = (0..count).map(|_| rng.below(10_001)).collect();
+            let favoured = rng.below(count as u64) as usize;
+
+            let parts = allocate_largest_remainder(gross, &weights, favoured).unwrap();
+            let total_weight: u128 = weights.iter().map(|&weight| weight as u128).sum();
+            if total_weight == 0 {
+                assert!(parts.iter().all(|&part| part == 0));
+                continue;
+            }
+
+            let sum: u128 = parts.iter().map(|&part| part as u128).sum();
+            assert_eq!(sum, gross as u128);
+
+            // Each part is its exact share rounded down, or one more
+            for (&part, &weight) in parts.iter().zip(&weights) {
+                let floor = (gross as u128 * weight as u128 / total_weight) as u64;
+                assert!(part == floor || part == floor + 1);
+            }
+        }
+    }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum AuctionStatus {
-    Active,
-    Ended,
-    Cancelled,
-}
+    #[test]
+    fn largest_remainder_ties_go_to_favoured() {
+        assert_eq!(
+            allocate_largest_remainder(1, &[1, 1], 0).unwrap(),
+            vec![1, 0]
+        );
+        assert_eq!(
+            allocate_largest_remainder(1, &[1, 1], 1).unwrap(),
+            vec![0, 1]
+        );
+        assert_eq!(
+            allocate_largest_remainder(7, &[0, 0], 1).unwrap(),
+            vec![0, 0]
+        );
+    }
 
-impl AuctionHouse {
-    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 32 + 1 + 1 + 2 + 1 + 1 + 1;
-}
+    #[test]
+    fn split_sale_conserves_the_price() {
+        let mut rng = Rng(0xfee5_0f5a_1e00_0001);
+        for rounding_policy in [
+            RoundingPolicy::RemainderToSeller,
+            RoundingPolicy::RemainderToTreasury,
+        ] {
+            let mut auction_house = zeroed_auction_house(rounding_policy);
+            for _ in 0..10_000 {
+                let price = rng.price();
+                let fee_basis_points = rng.below(10_001) as u16;
+                auction_house.protocol_fee_basis_points =
+                    rng.below(10_001 - fee_basis_points as u64) as u16;
+                let royalty_basis_points = rng.below(10_001) as u16;
 
-impl Auction {
-    pub const LEN: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 33 + 1 + 1;
-}
+                let (fee, protocol_fee, royalties, proceeds) = auction_house
+                    .split_sale(price, fee_basis_points, royalty_basis_points)
+                    .unwrap();
+                let sum = fee as u128 + protocol_fee as u128 + royalties as u128 + proceeds as u128;
+                assert_eq!(sum, price as u128);
+            }
+        }
+    }
 
-impl Bid {
-    pub const LEN: usize = 8 + 32 + 32 + 8 + 8;
-}
+    #[test]
+    fn split_sale_caps_royalties_at_what_the_fees_leave() {
+        let mut auction_house = zeroed_auction_house(RoundingPolicy::RemainderToSeller);
+        auction_house.protocol_fee_basis_points = 100;
 
-#[error_code]
-pub enum AuctionHouseError {
-    #[msg("Auction is not active")]
-    AuctionNotActive,
-    #[msg("Auction has ended")]
-    AuctionEnded,
-    #[msg("Auction has not ended yet")]
-    AuctionNotEnded,
-    #[msg("Bid amount is too low")]
-    BidTooLow,
-    #[msg("Unauthorized")]
-    Unauthorized,
-} 
\ No newline at end of file
+        assert_eq!(
+            auction_house.split_sale(1_000_000, 250, 10_000).unwrap(),
+            (25_000, 10_000, 965_000, 0)
+        );
+        assert_eq!(
+            auction_house.split_sale(1_000_000, 0, 10_000).unwrap(),
+            (0, 10_000, 990_000, 0)
+        );
+        assert_eq!(
+            auction_house.split_sale(1_000_000, 250, 500).unwrap(),
+            (25_000, 10_000, 50_000, 915_000)
+        );
+    }
+
+    #[test]
+    fn split_premium_sale_conserves_price_and_premium() {
+        let mut rng = Rng(0xb0ff_e75a_1e50_0001);
+        for rounding_policy in [
+            RoundingPolicy::RemainderToSeller,
+            RoundingPolicy::RemainderToTreasury,
+        ] {
+            let mut auction_house = zeroed_auction_house(rounding_policy);
+            for _ in 0..10_000 {
+                let price = rng.price();
+                let premium = rng.price();
+                let premium_basis_points = rng.below(10_001) as u16;
+                auction_house.protocol_fee_basis_points = rng.below(10_001) as u16;
+                let royalty_basis_points = rng.below(10_001) as u16;
+
+                let (fee, protocol_fee, royalties, proceeds) = auction_house
+                    .split_premium_sale(price, premium, premium_basis_points, royalty_basis_points)
+                    .unwrap();
+                assert_eq!(royalties as u128 + proceeds as u128, price as u128);
+                if premium_basis_points > 0 {
+                    assert_eq!(fee as u128 + protocol_fee as u128, premium as u128);
+                } else {
+                    assert_eq!((fee, protocol_fee), (0, 0));
+                }
+            }
+        }
+    }
+
+    /// Program that stands in for a house's notify_program; the cluster records
+    /// every CPI it receives
+    const MOCK_NOTIFY_PROGRAM: Pubkey = Pubkey::new_from_array([7; 32]);
+
+    thread_local! {
+        static NOW: Cell<i64> = const { Cell::new(0) };
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+        static NOTIFICATIONS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Syscalls of the in-process cluster. The clock reads NOW, events and notify
+    /// CPIs are recorded, and token and system CPIs run in place after the signers
+    /// are checked as the runtime would.
+    struct TestSyscalls;
+
+    impl SyscallStubs for TestSyscalls {
+        fn sol_log(&self, _message: &str) {}
+
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                unix_timestamp: NOW.with(Cell::get),
+                ..Clock::default()
+            };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            0
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let pda_signers = signers_seeds
+                .iter()
+                .map(|seeds| Pubkey::create_program_address(seeds, &crate::ID))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| ProgramError::InvalidSeeds)?;
+            let mut accounts = Vec::with_capacity(instruction.accounts.len());
+            for meta in &instruction.accounts {
+                let mut account = account_infos
+                    .iter()
+                    .find(|account| *account.key == meta.pubkey)
+                    .ok_or(ProgramError::NotEnoughAccountKeys)?
+                    .clone();
+                if meta.is_signer && !account.is_signer && !pda_signers.contains(&meta.pubkey) {
+                    return Err(ProgramError::MissingRequiredSignature);
+                }
+                account.is_signer = meta.is_signer;
+                account.is_writable = meta.is_writable;
+                accounts.push(account);
+            }
+
+            let program_id = instruction.program_id;
+            if program_id == spl_token::ID {
+                spl_token::processor::Processor::process(&program_id, &accounts, &instruction.data)
+            } else if program_id == system_program::ID {
+                process_system_instruction(&accounts, &instruction.data)
+            } else if program_id == MOCK_NOTIFY_PROGRAM {
+                NOTIFICATIONS.with(|notifications| {
+                    notifications.borrow_mut().push(instruction.data.clone())
+                });
+                Ok(())
+            } else {
+                Err(ProgramError::IncorrectProgramId)
+            }
+        }
+    }
+
+    /// The system program instructions Anchor's account constraints use
+    fn process_system_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+        let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| {
+            **from.try_borrow_mut_lamports()? = from
+                .lamports()
+                .checked_sub(lamports)
+                .ok_or(ProgramError::InsufficientFunds)?;
+            **to.try_borrow_mut_lamports()? += lamports;
+            Ok::<(), ProgramError>(())
+        };
+        match limited_deserialize(data, data.len() as u64)
+            .map_err(|_| ProgramError::InvalidInstructionData)?
+        {
+            SystemInstruction::CreateAccount {
+                lamports,
+                space,
+                owner,
+            } => {
+                transfer(&accounts[0], &accounts[1], lamports)?;
+                accounts[1].realloc(space as usize, true)?;
+                accounts[1].assign(&owner);
+            }
+            SystemInstruction::Transfer { lamports } => {
+                transfer(&accounts[0], &accounts[1], lamports)?
+            }
+            SystemInstruction::Allocate { space } => accounts[0].realloc(space as usize, true)?,
+            SystemInstruction::Assign { owner } => accounts[0].assign(&owner),
+            _ => return Err(ProgramError::InvalidInstructionData),
+        }
+        Ok(())
+    }
+
+    /// Key of an account with the original data length the runtime keeps just
+    /// before it, which AccountInfo::realloc reads
+    #[repr(C)]
+    struct KeySlot {
+        original_data_len: u32,
+        key: Pubkey,
+    }
+
+    /// Accounts of an in-process cluster that runs instructions through entry. Every
+    /// account is leaked, so instructions can borrow them for 'static.
+    struct Cluster {
+        accounts: HashMap<Pubkey, AccountInfo<'static>>,
+    }
+
+    impl Cluster {
+        fn new(now: i64) -> Self {
+            static STUBS: Once = Once::new();
+            STUBS.call_once(|| {
+                set_syscall_stubs(Box::new(TestSyscalls));
+            });
+            NOW.with(|clock| clock.set(now));
+            EVENTS.with(|events| events.borrow_mut().clear());
+            NOTIFICATIONS.with(|notifications| notifications.borrow_mut().clear());
+
+            let mut cluster = Cluster {
+                accounts: HashMap::new(),
+            };
+            for program in [
+                crate::ID,
+                system_program::ID,
+                spl_token::ID,
+                MOCK_NOTIFY_PROGRAM,
+            ] {
+                cluster.insert(program, bpf_loader::ID, 1, &[], true);
+            }
+            let rent = Rent::default();
+            let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+            rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+            rent_data.push(rent.burn_percent);
+            cluster.insert(sysvar::rent::ID, sysvar::ID, 1, &rent_data, false);
+            cluster
+        }
+
+        fn now(&self) -> i64 {
+            NOW.with(Cell::get)
+        }
+
+        fn warp(&self, now: i64) {
+            NOW.with(|clock| clock.set(now));
+        }
+
+        fn insert(
+            &mut self,
+            key: Pubkey,
+            owner: Pubkey,
+            lamports: u64,
+            data: &[u8],
+            executable: bool,
+        ) -> Pubkey {
+            let slot = Box::leak(Box::new(KeySlot {
+                original_data_len: data.len() as u32,
+                key,
+            }));
+            // Like the runtime's input buffer, the length sits in the 8 bytes before the
+            // data, with room after it for realloc to grow into
+            let words = (8 + data.len() + MAX_PERMITTED_DATA_INCREASE) / 8 + 1;
+            let buffer = Box::leak(vec![0u64; words].into_boxed_slice());
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, words * 8)
+            };
+            bytes[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes[8..8 + data.len()].copy_from_slice(data);
+            let account = AccountInfo::new(
+                &slot.key,
+                false,
+                false,
+                Box::leak(Box::new(lamports)),
+                &mut bytes[8..8 + data.len()],
+                Box::leak(Box::new(owner)),
+                executable,
+                0,
+            );
+            self.accounts.insert(key, account);
+            key
+        }
+
+        fn add_wallet(&mut self) -> Pubkey {
+            self.insert(
+                Pubkey::new_unique(),
+                system_program::ID,
+                100_000_000_000,
+                &[],
+                false,
+            )
+        }
+
+        fn add_mint(&mut self, decimals: u8, supply: u64) -> Pubkey {
+            let mut data = vec![0; spl_token::state::Mint::LEN];
+            spl_token::state::Mint {
+                mint_authority: COption::None,
+                supply,
+                decimals,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            }
+            .pack_into_slice(&mut data);
+            let lamports = Rent::default().minimum_balance(data.len());
+            self.insert(Pubkey::new_unique(), spl_token::ID, lamports, &data, false)
+        }
+
+        fn add_token_account_at(
+            &mut self,
+            key: Pubkey,
+            mint: Pubkey,
+            owner: Pubkey,
+            amount: u64,
+        ) -> Pubkey {
+            let mut data = vec![0; spl_token::state::Account::LEN];
+            spl_token::state::Account {
+                mint,
+                owner,
+                amount,
+                delegate: COption::None,
+                state: spl_token::state::AccountState::Initialized,
+                is_native: COption::None,
+                delegated_amount: 0,
+                close_authority: COption::None,
+            }
+            .pack_into_slice(&mut data);
+            let lamports = Rent::default().minimum_balance(data.len());
+            self.insert(key, spl_token::ID, lamports, &data, false)
+        }
+
+        fn add_token_account(&mut self, mint: Pubkey, owner: Pubkey, amount: u64) -> Pubkey {
+            self.add_token_account_at(Pubkey::new_unique(), mint, owner, amount)
+        }
+
+        /// Writes a program account at `key`, padded out to `space`
+        fn add_program_account<T: AccountSerialize>(
+            &mut self,
+            key: Pubkey,
+            account: &T,
+            space: usize,
+        ) -> Pubkey {
+            let mut data = Vec::with_capacity(space);
+            account.try_serialize(&mut data).unwrap();
+            data.resize(space, 0);
+            let lamports = Rent::default().minimum_balance(space);
+            self.insert(key, crate::ID, lamports, &data, false)
+        }
+
+        fn read<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+            T::try_deserialize(&mut &self.accounts[key].data.borrow()[..]).unwrap()
+        }
+
+        fn token_balance(&self, key: &Pubkey) -> u64 {
+            spl_token::state::Account::unpack(&self.accounts[key].data.borrow())
+                .unwrap()
+                .amount
+        }
+
+        /// Runs one instruction through entry. Accounts the cluster has never seen
+        /// start out as empty system accounts, as new PDAs do.
+        fn process<A: ToAccountMetas, D: InstructionData>(
+            &mut self,
+            accounts: A,
+            data: D,
+            remaining_accounts: &[AccountMeta],
+        ) -> ProgramResult {
+            let mut metas = accounts.to_account_metas(None);
+            metas.extend_from_slice(remaining_accounts);
+            let mut infos = Vec::with_capacity(metas.len());
+            for meta in metas {
+                if !self.accounts.contains_key(&meta.pubkey) {
+                    self.insert(meta.pubkey, system_program::ID, 0, &[], false);
+                }
+                let mut info = self.accounts[&meta.pubkey].clone();
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                infos.push(info);
+            }
+
+            // A failed instruction leaves no trace, as its transaction would not land
+            let snapshot: Vec<_> = self
+                .accounts
+                .values()
+                .map(|account| {
+                    let data = account.data.borrow().to_vec();
+                    (account.clone(), account.lamports(), data, *account.owner)
+                })
+                .collect();
+            let event_count = EVENTS.with(|events| events.borrow().len());
+            let result = entry(&crate::ID, Vec::leak(infos), &data.data());
+            if result.is_err() {
+                for (account, lamports, data, owner) in snapshot {
+                    **account.lamports.borrow_mut() = lamports;
+                    account.realloc(data.len(), false).unwrap();
+                    account.data.borrow_mut().copy_from_slice(&data);
+                    account.assign(&owner);
+                }
+                EVENTS.with(|events| events.borrow_mut().truncate(event_count));
+            }
+            result
+        }
+    }
+
+    fn program_error(error: AuctionHouseError) -> ProgramError {
+        anchor_lang::error::Error::from(error).into()
+    }
+
+    /// Metaplex metadata for `mint` paying `seller_fee_basis_points` to `creators`
+    /// by share
+    fn metadata_bytes(
+        mint: &Pubkey,
+        seller_fee_basis_points: u16,
+        creators: &[(Pubkey, u8)],
+    ) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for field in ["", "", ""] {
+            field.to_string().serialize(&mut data).unwrap();
+        }
+        seller_fee_basis_points.serialize(&mut data).unwrap();
+        let creators: Vec<(Pubkey, bool, u8)> = creators
+            .iter()
+            .map(|&(address, share)| (address, true, share))
+            .collect();
+        Some(creators).serialize(&mut data).unwrap();
+        // primary_sale_happened, is_mutable, edition_nonce, token_standard, collection
+        data.extend_from_slice(&[0, 1, 0, 0, 0]);
+        data
+    }
+
+    /// A house charging 2.5% in a 6-decimal treasury mint, written straight into the
+    /// cluster, that sellers list on through create_auction
+    struct Market {
+        cluster: Cluster,
+        authority: Pubkey,
+        auction_house: Pubkey,
+        treasury_mint: Pubkey,
+        treasury: Pubkey,
+        fee_account: Pubkey,
+        fee_destination: Pubkey,
+        /// Treasury mint token account of every wallet funded through fund
+        token_accounts: HashMap<Pubkey, Pubkey>,
+    }
+
+    /// Accounts of an auction listed through Market::list
+    struct Listing {
+        auction: Pubkey,
+        nft_mint: Pubkey,
+        seller_nft_account: Pubkey,
+        auction_nft_account: Pubkey,
+        auction_token_account: Pubkey,
+        metadata: Pubkey,
+        seller_index: Pubkey,
+    }
+
+    impl Market {
+        const START: i64 = 1_000;
+
+        fn new(configure: impl FnOnce(&mut AuctionHouse)) -> Self {
+            let mut cluster = Cluster::new(Self::START);
+            let authority = cluster.add_wallet();
+            let (auction_house, bump) =
+                Pubkey::find_program_address(&[b"auction_house", authority.as_ref()], &crate::ID);
+            let treasury_mint = cluster.add_mint(6, 0);
+            let (fee_account, fee_payer_bump) = Pubkey::find_program_address(
+                &[b"auction_house_fee_account", auction_house.as_ref()],
+                &crate::ID,
+            );
+            let (treasury, treasury_bump) = Pubkey::find_program_address(
+                &[b"auction_house_treasury", auction_house.as_ref()],
+                &crate::ID,
+            );
+            cluster.add_token_account_at(fee_account, treasury_mint, auction_house, 0);
+            cluster.add_token_account_at(treasury, treasury_mint, auction_house, 0);
+            let fee_destination = cluster.add_token_account(treasury_mint, authority, 0);
+
+            let mut house = zeroed_auction_house(RoundingPolicy::RemainderToSeller);
+            house.authority = authority;
+            house.creator = authority;
+            house.treasury_mint = treasury_mint;
+            house.auction_house_fee_account = fee_account;
+            house.auction_house_treasury = treasury;
+            house.fee_withdrawal_destination = fee_destination;
+            house.fee_payer_bump = fee_payer_bump;
+            house.treasury_bump = treasury_bump;
+            house.seller_fee_basis_points = 250;
+            house.absolute_max_bid = u64::MAX;
+            house.bump = bump;
+            configure(&mut house);
+            cluster.add_program_account(auction_house, &house, AuctionHouse::LEN);
+
+            Market {
+                cluster,
+                authority,
+                auction_house,
+                treasury_mint,
+                treasury,
+                fee_account,
+                fee_destination,
+                token_accounts: HashMap::new(),
+            }
+        }
+
+        /// A wallet holding `amount` of the treasury mint
+        fn fund(&mut self, amount: u64) -> Pubkey {
+            let wallet = self.cluster.add_wallet();
+            let token_account = self
+                .cluster
+                .add_token_account(self.treasury_mint, wallet, amount);
+            self.token_accounts.insert(wallet, token_account);
+            wallet
+        }
+
+        /// An English auction opening at 1 unit and closing 1000 seconds from now
+        fn english_args(&self) -> CreateAuctionArgs {
+            CreateAuctionArgs {
+                token_size: 1,
+                multi_unit: false,
+                minimum_price: 1_000_000,
+                opening_bid: 0,
+                min_bid_raw: 0,
+                end_time: self.cluster.now() + 1_000,
+                min_bid_increment: 1,
+                min_bid_increment_bps: 0,
+                increment_tiers: vec![],
+                extension_window: 0,
+                extension_amount: 0,
+                max_end_time: 0,
+                min_bid_interval: 0,
+                bid_fee: 0,
+                bid_bond: 0,
+                bond_claim_window: 0,
+                fee_override: None,
+                proceeds_destination: None,
+                buy_now_price: 0,
+                reserve_price: 0,
+                auction_type: AuctionType::EnglishAscending as u8,
+                pricing_rule: PricingRule::FirstPrice,
+                start_price: 0,
+                end_price: 0,
+                start_time: 0,
+                reveal_window: 0,
+                allowlist_root: [0; 32],
+                name: String::new(),
+                uri: String::new(),
+            }
+        }
+
+        /// Lists a fresh NFT for `seller` through create_auction, co-signed by the
+        /// house authority when the listing waives fees
+        fn list(&mut self, seller: Pubkey, args: CreateAuctionArgs) -> (Listing, ProgramResult) {
+            let nft_mint = self.cluster.add_mint(0, 1);
+            let seller_nft_account = self.cluster.add_token_account(nft_mint, seller, 1);
+            let (auction, auction_bump) = Pubkey::find_program_address(
+                &[b"auction", nft_mint.as_ref(), seller.as_ref()],
+                &crate::ID,
+            );
+            let (auction_nft_account, _) =
+                Pubkey::find_program_address(&[b"auction_nft", auction.as_ref()], &crate::ID);
+            let (metadata, _) = Pubkey::find_program_address(
+                &[b"metadata", METADATA_PROGRAM_ID.as_ref(), nft_mint.as_ref()],
+                &METADATA_PROGRAM_ID,
+            );
+            let (seller_index, _) = Pubkey::find_program_address(
+                &[
+                    b"seller_index",
+                    self.auction_house.as_ref(),
+                    seller.as_ref(),
+                ],
+                &crate::ID,
+            );
+            let auction_token_account =
+                self.cluster
+                    .add_token_account(self.treasury_mint, auction, 0);
+
+            let fee_override = args.fee_override;
+            let result = self.cluster.process(
+                accounts::CreateAuction {
+                    auction,
+                    auction_house: self.auction_house,
+                    registered_mint: None,
+                    token_mint: nft_mint,
+                    metadata,
+                    token_account: seller_nft_account,
+                    auction_nft_account,
+                    seller_index,
+                    treasury_mint: self.treasury_mint,
+                    authority: seller,
+                    auction_house_authority: fee_override.map(|_| self.authority),
+                    token_program: spl_token::ID,
+                    system_program: system_program::ID,
+                    rent: sysvar::rent::ID,
+                },
+                instruction::CreateAuction { auction_bump, args },
+                &[],
+            );
+            let listing = Listing {
+                auction,
+                nft_mint,
+                seller_nft_account,
+                auction_nft_account,
+                auction_token_account,
+                metadata,
+                seller_index,
+            };
+            (listing, result)
+        }
+
+        fn auction(&self, listing: &Listing) -> Auction {
+            self.cluster.read(&listing.auction)
+        }
+
+        fn bid_address(listing: &Listing, bidder: &Pubkey) -> Pubkey {
+            Pubkey::find_program_address(
+                &[b"bid", listing.auction.as_ref(), bidder.as_ref()],
+                &crate::ID,
+            )
+            .0
+        }
+
+        /// place_bid's accounts for `bidder`, refunding whoever currently leads
+        fn bid_accounts(&self, listing: &Listing, bidder: Pubkey) -> accounts::PlaceBid {
+            let previous = self
+                .auction(listing)
+                .highest_bidder
+                .filter(|previous| *previous != bidder);
+            accounts::PlaceBid {
+                auction: listing.auction,
+                auction_house: self.auction_house,
+                bid: Self::bid_address(listing, &bidder),
+                bidder_token_account: Some(self.token_accounts[&bidder]),
+                auction_token_account: Some(listing.auction_token_account),
+                previous_bid: previous.map(|previous| Self::bid_address(listing, &previous)),
+                previous_bidder_token_account: previous
+                    .map(|previous| self.token_accounts[&previous]),
+                previous_bidder: None,
+                treasury_mint: self.treasury_mint,
+                referrer: None,
+                loyalty: None,
+                bidder_escrow: None,
+                bidder_escrow_token_account: None,
+                notify_program: None,
+                bidder,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            }
+        }
+
+        fn bid(&mut self, listing: &Listing, bidder: Pubkey, bid_amount: u64) -> ProgramResult {
+            let accounts = self.bid_accounts(listing, bidder);
+            self.cluster.process(
+                accounts,
+                instruction::PlaceBid {
+                    bid_amount,
+                    max_bid: 0,
+                    proof: vec![],
+                },
+                &[],
+            )
+        }
+
+        /// Buys the auction outright at its buy now price, with `loyalty` as the
+        /// buyer's tier
+        fn buy_now(
+            &mut self,
+            listing: &Listing,
+            buyer: Pubkey,
+            loyalty: Option<Pubkey>,
+        ) -> ProgramResult {
+            let buyer_nft_account = self.cluster.add_token_account(listing.nft_mint, buyer, 0);
+            let auction = self.auction(listing);
+            let accounts = accounts::BuyNow {
+                auction: listing.auction,
+                buyer_token_account: Some(self.token_accounts[&buyer]),
+                auction_token_account: Some(listing.auction_token_account),
+                previous_bid: None,
+                previous_bidder_token_account: None,
+                previous_bidder: None,
+                auction_nft_account: listing.auction_nft_account,
+                buyer_nft_account,
+                auction_house: self.auction_house,
+                registered_mint: None,
+                auction_house_treasury: self.treasury,
+                protocol_fee_account: None,
+                seller_proceeds_account: Some(self.token_accounts[&auction.proceeds_destination]),
+                seller: None,
+                metadata: listing.metadata,
+                token_mint: listing.nft_mint,
+                treasury_mint: self.treasury_mint,
+                loyalty,
+                seller_index: Some(listing.seller_index),
+                buyer,
+                token_program: spl_token::ID,
+                treasury_token_program: spl_token::ID,
+                system_program: system_program::ID,
+            };
+            let data = instruction::BuyNow {
+                proof: vec![],
+                max_price: auction.buy_now_price,
+            };
+            self.cluster.process(accounts, data, &[])
+        }
+
+        /// end_auction's accounts, paying the seller's proceeds into their treasury
+        /// account
+        fn end_accounts(&self, listing: &Listing) -> accounts::EndAuction {
+            let auction = self.auction(listing);
+            accounts::EndAuction {
+                auction: listing.auction,
+                auction_house: self.auction_house,
+                registered_mint: None,
+                auction_nft_account: listing.auction_nft_account,
+                owner_token_account: listing.seller_nft_account,
+                auction_token_account: Some(listing.auction_token_account),
+                auction_house_treasury: self.treasury,
+                protocol_fee_account: None,
+                fee_withdrawal_destination: None,
+                seller_proceeds_account: self
+                    .token_accounts
+                    .get(&auction.proceeds_destination)
+                    .copied(),
+                seller: None,
+                referrer_token_account: None,
+                referrer: None,
+                winner_token_account: auction
+                    .highest_bidder
+                    .map(|winner| self.token_accounts[&winner]),
+                winner: None,
+                metadata: listing.metadata,
+                token_mint: listing.nft_mint,
+                treasury_mint: self.treasury_mint,
+                seller_index: Some(listing.seller_index),
+                settler: self.authority,
+                token_program: spl_token::ID,
+                treasury_token_program: spl_token::ID,
+            }
+        }
+
+        /// Settles the auction once it has closed, paying royalties into
+        /// `creator_accounts`
+        fn end(&mut self, listing: &Listing, creator_accounts: &[Pubkey]) -> ProgramResult {
+            let auction = self.auction(listing);
+            self.cluster.warp(auction.end_time);
+            let accounts = self.end_accounts(listing);
+            let creators: Vec<AccountMeta> = creator_accounts
+                .iter()
+                .map(|account| AccountMeta::new(*account, false))
+                .collect();
+            self.cluster
+                .process(accounts, instruction::EndAuction {}, &creators)
+        }
+    }
+
+    #[test]
+    fn seller_at_the_cap_cannot_list_more() {
+        let mut market = Market::new(|house| house.max_active_auctions_per_seller = 1);
+        let seller = market.fund(0);
+
+        market.list(seller, market.english_args()).1.unwrap();
+        let (listing, result) = market.list(seller, market.english_args());
+        assert_eq!(
+            result,
+            Err(program_error(AuctionHouseError::TooManyActiveAuctions))
+        );
+        let seller_index: SellerIndex = market.cluster.read(&listing.seller_index);
+        assert_eq!(seller_index.active_auctions, 1);
+    }
+
+    #[test]
+    fn final_auction_frees_a_slot() {
+        let mut market = Market::new(|house| house.max_active_auctions_per_seller = 1);
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        // Settlement must bring the seller index along while the auction holds a slot
+        let mut accounts = market.end_accounts(&listing);
+        accounts.seller_index = None;
+        market.cluster.warp(Market::START + 1_000);
+        assert_eq!(
+            market
+                .cluster
+                .process(accounts, instruction::EndAuction {}, &[]),
+            Err(program_error(AuctionHouseError::MissingSellerIndex))
+        );
+
+        market.end(&listing, &[]).unwrap();
+        assert!(!market.auction(&listing).holds_seller_slot);
+        let seller_index: SellerIndex = market.cluster.read(&listing.seller_index);
+        assert_eq!(seller_index.active_auctions, 0);
+        market.list(seller, market.english_args()).1.unwrap();
+    }
+
+    #[test]
+    fn listing_captures_the_treasury_decimals() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+        assert_eq!(market.auction(&listing).treasury_decimals, 6);
+    }
+
+    #[test]
+    fn sealed_bids_are_held_to_the_ceiling() {
+        let mut market = Market::new(|house| house.absolute_max_bid = 5_000_000);
+        let seller = market.fund(0);
+        let mut args = market.english_args();
+        args.auction_type = AuctionType::SealedSecondPrice as u8;
+        args.reveal_window = 100;
+        let (listing, result) = market.list(seller, args);
+        result.unwrap();
+
+        let bidder = market.fund(10_000_000);
+        let (bid, _) = Pubkey::find_program_address(
+            &[b"bid", listing.auction.as_ref(), bidder.as_ref()],
+            &crate::ID,
+        );
+        let nonce = [9; 32];
+        let commitment = hashv(&[&5_000_000u64.to_le_bytes(), &nonce]).to_bytes();
+        let commit = |market: &mut Market, collateral: u64| {
+            let accounts = accounts::CommitBid {
+                auction: listing.auction,
+                auction_house: market.auction_house,
+                bid,
+                bidder_token_account: Some(market.token_accounts[&bidder]),
+                auction_token_account: Some(listing.auction_token_account),
+                treasury_mint: market.treasury_mint,
+                bidder,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                rent: sysvar::rent::ID,
+            };
+            let data = instruction::CommitBid {
+                commitment,
+                collateral,
+                proof: vec![],
+            };
+            market.cluster.process(accounts, data, &[])
+        };
+        assert_eq!(
+            commit(&mut market, 5_000_001),
+            Err(program_error(AuctionHouseError::BidExceedsCeiling))
+        );
+        commit(&mut market, 5_000_000).unwrap();
+
+        // A ceiling lowered after the commitment still binds the reveal
+        let mut house: AuctionHouse = market.cluster.read(&market.auction_house);
+        house.absolute_max_bid = 4_000_000;
+        market
+            .cluster
+            .add_program_account(market.auction_house, &house, AuctionHouse::LEN);
+        market.cluster.warp(market.auction(&listing).end_time);
+        let accounts = accounts::RevealBid {
+            auction: listing.auction,
+            auction_house: market.auction_house,
+            bid,
+            bidder,
+        };
+        let data = instruction::RevealBid {
+            amount: 5_000_000,
+            nonce,
+        };
+        assert_eq!(
+            market.cluster.process(accounts, data, &[]),
+            Err(program_error(AuctionHouseError::BidExceedsCeiling))
+        );
+    }
+
+    /// The money-moving events an indexer builds its ledger from
+    #[derive(Debug, PartialEq)]
+    enum LedgerEntry {
+        Refund(Pubkey, u64),
+        Royalty(Pubkey, u64),
+        Fee(u8, Pubkey, u64),
+        Withdrawal(Pubkey, Pubkey, u64),
+    }
+
+    fn ledger() -> Vec<LedgerEntry> {
+        EVENTS.with(|events| {
+            events
+                .borrow()
+                .iter()
+                .filter_map(|event| {
+                    let (discriminator, mut data) = event.split_at(8);
+                    if discriminator == BidRefunded::DISCRIMINATOR {
+                        let event = BidRefunded::deserialize(&mut data).unwrap();
+                        Some(LedgerEntry::Refund(event.bidder, event.amount))
+                    } else if discriminator == RoyaltyPaid::DISCRIMINATOR {
+                        let event = RoyaltyPaid::deserialize(&mut data).unwrap();
+                        Some(LedgerEntry::Royalty(event.creator, event.amount))
+                    } else if discriminator == FeePaid::DISCRIMINATOR {
+                        let event = FeePaid::deserialize(&mut data).unwrap();
+                        Some(LedgerEntry::Fee(
+                            event.kind as u8,
+                            event.recipient,
+                            event.amount,
+                        ))
+                    } else if discriminator == TreasuryWithdrawn::DISCRIMINATOR {
+                        let event = TreasuryWithdrawn::deserialize(&mut data).unwrap();
+                        Some(LedgerEntry::Withdrawal(
+                            event.treasury,
+                            event.destination,
+                            event.amount,
+                        ))
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+    }
+
+    #[test]
+    fn full_auction_emits_its_money_flow() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+        let creator = market.fund(0);
+        let metadata = metadata_bytes(&listing.nft_mint, 500, &[(creator, 100)]);
+        market
+            .cluster
+            .insert(listing.metadata, METADATA_PROGRAM_ID, 1, &metadata, false);
+
+        let alice = market.fund(10_000_000);
+        let bob = market.fund(10_000_000);
+        market.bid(&listing, alice, 1_500_000).unwrap();
+        market.bid(&listing, bob, 2_000_000).unwrap();
+        let creator_account = market.token_accounts[&creator];
+        market.end(&listing, &[creator_account]).unwrap();
+
+        // Fees the house collected elsewhere wait in the fee account
+        market.cluster.add_token_account_at(
+            market.fee_account,
+            market.treasury_mint,
+            market.auction_house,
+            30_000,
+        );
+        let accounts = accounts::WithdrawFromTreasury {
+            auction_house: market.auction_house,
+            registered_mint: None,
+            auction_house_treasury: market.treasury,
+            fee_withdrawal_destination: market.fee_destination,
+            treasury_mint: market.treasury_mint,
+            authority: market.authority,
+            token_program: spl_token::ID,
+        };
+        let data = instruction::WithdrawFromTreasury { amount: 50_000 };
+        market.cluster.process(accounts, data, &[]).unwrap();
+        let accounts = accounts::WithdrawFromFeeAccount {
+            auction_house: market.auction_house,
+            auction_house_fee_account: market.fee_account,
+            fee_withdrawal_destination: market.fee_destination,
+            treasury_mint: market.treasury_mint,
+            authority: market.authority,
+            token_program: spl_token::ID,
+        };
+        let data = instruction::WithdrawFromFeeAccount { amount: 30_000 };
+        market.cluster.process(accounts, data, &[]).unwrap();
+
+        assert_eq!(
+            ledger(),
+            vec![
+                LedgerEntry::Refund(alice, 1_500_000),
+                LedgerEntry::Royalty(creator, 100_000),
+                LedgerEntry::Fee(FeeKind::House as u8, market.treasury, 50_000),
+                LedgerEntry::Withdrawal(market.treasury, market.fee_destination, 50_000),
+                LedgerEntry::Withdrawal(market.fee_account, market.fee_destination, 30_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn outbid_penny_bidder_is_notified() {
+        let mut market = Market::new(|house| house.notify_program = Some(MOCK_NOTIFY_PROGRAM));
+        let seller = market.fund(0);
+        let mut args = market.english_args();
+        args.auction_type = AuctionType::PennyAuction as u8;
+        args.min_bid_increment = 10_000;
+        args.extension_amount = 10;
+        let (listing, result) = market.list(seller, args);
+        result.unwrap();
+
+        let alice = market.fund(5_000_000);
+        let bob = market.fund(5_000_000);
+        for bidder in [alice, bob] {
+            let previous = market.auction(&listing).highest_bidder;
+            let accounts = accounts::PlacePennyBid {
+                auction: listing.auction,
+                auction_house: market.auction_house,
+                registered_mint: None,
+                auction_house_treasury: market.treasury,
+                bid: Market::bid_address(&listing, &bidder),
+                bidder_token_account: Some(market.token_accounts[&bidder]),
+                auction_token_account: Some(listing.auction_token_account),
+                previous_bid: previous.map(|previous| Market::bid_address(&listing, &previous)),
+                previous_bidder_token_account: previous
+                    .map(|previous| market.token_accounts[&previous]),
+                previous_bidder: None,
+                treasury_mint: market.treasury_mint,
+                notify_program: Some(MOCK_NOTIFY_PROGRAM),
+                bidder,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+            };
+            let data = instruction::PlacePennyBid { proof: vec![] };
+            market.cluster.process(accounts, data, &[]).unwrap();
+        }
+
+        // Only bob's bid outbid anyone
+        let price = market.auction(&listing).current_price;
+        let mut expected = OUTBID_NOTIFICATION_TAG.to_vec();
+        expected.extend_from_slice(listing.auction.as_ref());
+        expected.extend_from_slice(alice.as_ref());
+        expected.extend_from_slice(&price.to_le_bytes());
+        NOTIFICATIONS.with(|notifications| assert_eq!(*notifications.borrow(), vec![expected]));
+    }
+
+    #[test]
+    fn loyalty_tier_rebates_part_of_the_buy_now_fee() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let mut args = market.english_args();
+        args.buy_now_price = 2_000_000;
+        let (tiered_listing, result) = market.list(seller, args);
+        result.unwrap();
+        let mut args = market.english_args();
+        args.buy_now_price = 2_000_000;
+        let (untiered_listing, result) = market.list(seller, args);
+        result.unwrap();
+
+        let tiered = market.fund(2_000_000);
+        let (loyalty, loyalty_bump) = Pubkey::find_program_address(
+            &[b"loyalty", market.auction_house.as_ref(), tiered.as_ref()],
+            &crate::ID,
+        );
+        let accounts = accounts::SetLoyaltyTier {
+            loyalty,
+            auction_house: market.auction_house,
+            bidder: tiered,
+            authority: market.authority,
+            system_program: system_program::ID,
+        };
+        let data = instruction::SetLoyaltyTier {
+            loyalty_bump,
+            rebate_basis_points: 2_000,
+        };
+        market.cluster.process(accounts, data, &[]).unwrap();
+
+        // A fifth of the 50_000 fee goes back to the tiered buyer
+        market
+            .buy_now(&tiered_listing, tiered, Some(loyalty))
+            .unwrap();
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&tiered]),
+            10_000
+        );
+        assert_eq!(market.cluster.token_balance(&market.treasury), 40_000);
+
+        let untiered = market.fund(2_000_000);
+        market.buy_now(&untiered_listing, untiered, None).unwrap();
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&untiered]),
+            0
+        );
+        assert_eq!(market.cluster.token_balance(&market.treasury), 90_000);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            2 * 1_950_000
+        );
+    }
+
+    #[test]
+    fn two_creators_split_royalties_by_share() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+        let first_creator = market.fund(0);
+        let second_creator = market.fund(0);
+        let metadata = metadata_bytes(
+            &listing.nft_mint,
+            1_000,
+            &[(first_creator, 60), (second_creator, 40)],
+        );
+        market
+            .cluster
+            .insert(listing.metadata, METADATA_PROGRAM_ID, 1, &metadata, false);
+
+        let bidder = market.fund(2_000_000);
+        market.bid(&listing, bidder, 2_000_000).unwrap();
+        let creator_accounts = [
+            market.token_accounts[&first_creator],
+            market.token_accounts[&second_creator],
+        ];
+        market.end(&listing, &creator_accounts).unwrap();
+
+        // 10% royalties of 200_000, then the 2.5% house fee, leave the seller the rest
+        assert_eq!(market.cluster.token_balance(&creator_accounts[0]), 120_000);
+        assert_eq!(market.cluster.token_balance(&creator_accounts[1]), 80_000);
+        assert_eq!(market.cluster.token_balance(&market.treasury), 50_000);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            1_750_000
+        );
+    }
+
+    #[test]
+    fn seller_pays_model_takes_the_fee_from_proceeds() {
+        let mut market = Market::new(|house| house.fee_payer_model = FeePayerModel::SellerPays);
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        let bidder = market.fund(2_000_000);
+        market.bid(&listing, bidder, 2_000_000).unwrap();
+        market.end(&listing, &[]).unwrap();
+
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            1_950_000
+        );
+        assert_eq!(market.cluster.token_balance(&market.treasury), 50_000);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&bidder]),
+            0
+        );
+    }
+
+    #[test]
+    fn buyer_pays_model_charges_the_fee_on_top() {
+        let mut market = Market::new(|house| house.fee_payer_model = FeePayerModel::BuyerPays);
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        // The winner escrows the fee along with the bid
+        let bidder = market.fund(2_050_000);
+        market.bid(&listing, bidder, 2_000_000).unwrap();
+        assert_eq!(
+            market.cluster.token_balance(&listing.auction_token_account),
+            2_050_000
+        );
+        market.end(&listing, &[]).unwrap();
+
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            2_000_000
+        );
+        assert_eq!(market.cluster.token_balance(&market.treasury), 50_000);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&bidder]),
+            0
+        );
+    }
+
+    #[test]
+    fn buyer_pays_escrow_must_cover_the_fee() {
+        let mut market = Market::new(|house| house.fee_payer_model = FeePayerModel::BuyerPays);
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        // A wallet holding only the bid cannot escrow the fee on top of it
+        let short = market.fund(2_000_000);
+        assert_eq!(
+            market.bid(&listing, short, 2_000_000),
+            Err(ProgramError::Custom(
+                spl_token::error::TokenError::InsufficientFunds as u32
+            ))
+        );
+
+        // Nor is a sale settled out of an escrow that no longer covers it
+        let bidder = market.fund(2_050_000);
+        market.bid(&listing, bidder, 2_000_000).unwrap();
+        market.cluster.add_token_account_at(
+            listing.auction_token_account,
+            market.treasury_mint,
+            listing.auction,
+            2_000_000,
+        );
+        assert_eq!(
+            market.end(&listing, &[]),
+            Err(program_error(AuctionHouseError::InsufficientEscrow))
+        );
+    }
+
+    #[test]
+    fn fee_exempt_auction_settles_without_a_fee() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let mut args = market.english_args();
+        args.fee_override = Some(0);
+        let (exempt, result) = market.list(seller, args);
+        result.unwrap();
+        let (normal, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        let first_bidder = market.fund(2_000_000);
+        let second_bidder = market.fund(2_000_000);
+        market.bid(&exempt, first_bidder, 2_000_000).unwrap();
+        market.bid(&normal, second_bidder, 2_000_000).unwrap();
+
+        market.end(&exempt, &[]).unwrap();
+        assert_eq!(market.cluster.token_balance(&market.treasury), 0);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            2_000_000
+        );
+
+        // Without an override the house default of 2.5% applies
+        market.end(&normal, &[]).unwrap();
+        assert_eq!(market.cluster.token_balance(&market.treasury), 50_000);
+        assert_eq!(
+            market
+                .cluster
+                .token_balance(&market.token_accounts[&seller]),
+            2_000_000 + 1_950_000
+        );
+    }
+
+    /// Writes `value` at `key` under an original layout, as the first release did
+    fn add_original_account<T: AnchorSerialize>(
+        cluster: &mut Cluster,
+        key: Pubkey,
+        discriminator: &[u8],
+        value: &T,
+        len: usize,
+    ) {
+        let mut data = discriminator.to_vec();
+        value.serialize(&mut data).unwrap();
+        data.resize(len, 0);
+        let lamports = Rent::default().minimum_balance(len);
+        cluster.insert(key, crate::ID, lamports, &data, false);
+    }
+
+    fn migrate_auction(
+        cluster: &mut Cluster,
+        auction: Pubkey,
+        auction_house: Option<Pubkey>,
+        authority: Pubkey,
+    ) -> ProgramResult {
+        let accounts = accounts::MigrateAuction {
+            auction,
+            auction_house,
+            authority,
+            system_program: system_program::ID,
+        };
+        cluster.process(accounts, instruction::MigrateAuction {}, &[])
+    }
+
+    #[test]
+    fn original_auction_migrates_to_the_current_layout() {
+        let mut market = Market::new(|_| {});
+        let seller = market.cluster.add_wallet();
+        let bidder = market.cluster.add_wallet();
+        let (token_mint, token_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let auction = Pubkey::new_unique();
+        let original = AuctionV1 {
+            authority: seller,
+            token_mint,
+            token_account,
+            treasury_mint: market.treasury_mint,
+            token_size: 1,
+            minimum_price: 1_000_000,
+            current_price: 1_500_000,
+            end_time: Market::START + 500,
+            highest_bidder: Some(bidder),
+            status: 0,
+            bump: 254,
+        };
+        add_original_account(
+            &mut market.cluster,
+            auction,
+            &Auction::DISCRIMINATOR,
+            &original,
+            AuctionV1::LEN,
+        );
+
+        // The original layout records no house, so only the seller can name it
+        assert_eq!(
+            migrate_auction(&mut market.cluster, auction, None, seller),
+            Err(program_error(AuctionHouseError::MissingAuctionHouse))
+        );
+        assert_eq!(
+            migrate_auction(
+                &mut market.cluster,
+                auction,
+                Some(market.auction_house),
+                market.authority
+            ),
+            Err(program_error(AuctionHouseError::Unauthorized))
+        );
+        migrate_auction(
+            &mut market.cluster,
+            auction,
+            Some(market.auction_house),
+            seller,
+        )
+        .unwrap();
+
+        let account = &market.cluster.accounts[&auction];
+        assert_eq!(account.data_len(), Auction::LEN);
+        assert!(account.lamports() >= Rent::default().minimum_balance(Auction::LEN));
+        let migrated: Auction = market.cluster.read(&auction);
+        assert_eq!(migrated.version, AUCTION_VERSION);
+        assert_eq!(migrated.authority, seller);
+        assert_eq!(migrated.auction_house, market.auction_house);
+        assert_eq!(migrated.token_mint, token_mint);
+        assert_eq!(migrated.token_account, token_account);
+        assert_eq!(migrated.treasury_mint, market.treasury_mint);
+        assert_eq!(migrated.token_size, 1);
+        assert_eq!(migrated.minimum_price, 1_000_000);
+        assert_eq!(migrated.current_price, 1_500_000);
+        assert_eq!(migrated.end_time, Market::START + 500);
+        assert_eq!(migrated.highest_bidder, Some(bidder));
+        assert!(migrated.status == AuctionStatus::Active);
+        assert_eq!(migrated.bump, 254);
+        assert_eq!(migrated.proceeds_destination, seller);
+        assert_eq!(migrated.original_authority, seller);
+        assert_eq!(migrated.auction_type, AuctionType::EnglishAscending as u8);
+        assert_eq!((migrated.bid_count, migrated.reserve_price), (0, 0));
+
+        assert_eq!(
+            migrate_auction(
+                &mut market.cluster,
+                auction,
+                Some(market.auction_house),
+                seller
+            ),
+            Err(program_error(AuctionHouseError::AuctionAlreadyMigrated))
+        );
+    }
+
+    #[test]
+    fn versioned_auction_gains_the_fields_appended_since() {
+        let mut market = Market::new(|_| {});
+        let seller = market.fund(0);
+        let (listing, result) = market.list(seller, market.english_args());
+        result.unwrap();
+
+        // Rewrite the auction as version 5 left it, without original_authority
+        let mut auction = market.auction(&listing);
+        auction.version = 5;
+        auction.original_authority = Pubkey::default();
+        market
+            .cluster
+            .add_program_account(listing.auction, &auction, Auction::VERSIONED_LENS[3]);
+
+        migrate_auction(&mut market.cluster, listing.auction, None, seller).unwrap();
+        assert_eq!(
+            market.cluster.accounts[&listing.auction].data_len(),
+            Auction::LEN
+        );
+        let migrated = market.auction(&listing);
+        assert_eq!(migrated.version, AUCTION_VERSION);
+        assert_eq!(migrated.original_authority, seller);
+        assert_eq!(migrated.auction_house, market.auction_house);
+    }
+
+    #[test]
+    fn unrecognised_auction_layout_is_rejected() {
+        let mut market = Market::new(|_| {});
+        let seller = market.cluster.add_wallet();
+        let auction = Pubkey::new_unique();
+        add_original_account(
+            &mut market.cluster,
+            auction,
+            &Auction::DISCRIMINATOR,
+            &seller,
+            500,
+        );
+
+        assert_eq!(
+            migrate_auction(
+                &mut market.cluster,
+                auction,
+                Some(market.auction_house),
+                seller
+            ),
+            Err(program_error(AuctionHouseError::InvalidAuctionAccount))
+        );
+    }
+
+    #[test]
+    fn original_auction_house_migrates_to_the_current_layout() {
+        let mut market = Market::new(|_| {});
+        let authority = market.cluster.add_wallet();
+        let auction_house = Pubkey::new_unique();
+        let original = AuctionHouseV1 {
+            authority,
+            treasury_mint: market.treasury_mint,
+            auction_house_fee_account: Pubkey::new_unique(),
+            auction_house_treasury: Pubkey::new_unique(),
+            fee_withdrawal_destination: Pubkey::new_unique(),
+            fee_payer_bump: 253,
+            treasury_bump: 252,
+            seller_fee_basis_points: 300,
+            requires_sign_off: true,
+            can_change_sale_price: false,
+            bump: 251,
+        };
+        add_original_account(
+            &mut market.cluster,
+            auction_house,
+            &AuctionHouse::DISCRIMINATOR,
+            &original,
+            AuctionHouseV1::LEN,
+        );
+
+        let mut migrate = |authority: Pubkey| {
+            let accounts = accounts::MigrateAuctionHouse {
+                auction_house,
+                authority,
+                system_program: system_program::ID,
+            };
+            market
+                .cluster
+                .process(accounts, instruction::MigrateAuctionHouse {}, &[])
+        };
+        assert_eq!(
+            migrate(market.authority),
+            Err(program_error(AuctionHouseError::Unauthorized))
+        );
+        migrate(authority).unwrap();
+        assert_eq!(
+            migrate(authority),
+            Err(program_error(
+                AuctionHouseError::AuctionHouseAlreadyMigrated
+            ))
+        );
+
+        let migrated: AuctionHouse = market.cluster.read(&auction_house);
+        assert_eq!(migrated.authority, authority);
+        assert_eq!(migrated.creator, authority);
+        assert_eq!(migrated.treasury_mint, market.treasury_mint);
+        assert_eq!(
+            migrated.auction_house_fee_account,
+            original.auction_house_fee_account
+        );
+        assert_eq!(
+            migrated.auction_house_treasury,
+            original.auction_house_treasury
+        );
+        assert_eq!(
+            migrated.fee_withdrawal_destination,
+            original.fee_withdrawal_destination
+        );
+        assert_eq!(
+            (
+                migrated.fee_payer_bump,
+                migrated.treasury_bump,
+                migrated.bump
+            ),
+            (253, 252, 251)
+        );
+        assert_eq!(migrated.seller_fee_basis_points, 300);
+        assert!(migrated.requires_sign_off && !migrated.can_change_sale_price);
+        assert!(migrated.fee_payer_model == FeePayerModel::SellerPays);
+        assert_eq!(migrated.absolute_max_bid, u64::MAX);
+        assert_eq!(migrated.max_active_auctions_per_seller, 0);
+        assert_eq!(migrated.notify_program, None);
+    }
+}